@@ -1,4 +1,7 @@
 use super::*;
+use rand::{seq::SliceRandom, Rng};
+use snake::DIR;
+use std::collections::HashSet;
 
 pub struct ApplePlugin;
 
@@ -6,19 +9,28 @@ impl Plugin for ApplePlugin {
     fn build(&self, app: &mut App) {
         app.add_systems(
             Update,
-            apple_system
-                .run_if(in_state(GameState::InGame))
-                .after(snake::damage_snake_system)
-                .after(snake::snake_system)
-                .after(reset_game),
+            (
+                apple_system
+                    .run_if(in_state(GameState::InGame))
+                    .after(snake::damage_snake_system)
+                    .after(snake::snake_system)
+                    .after(reset_game),
+                apple_target_system
+                    .run_if(in_state(GameState::InGame))
+                    .after(snake::damage_snake_system)
+                    .before(apple_system),
+            ),
         );
     }
 }
 
-#[derive(Resource)]
+#[derive(Resource, Default)]
 pub struct Apples {
     pub list: HashMap<IVec2, Entity>,
     pub sprite: Option<Handle<Image>>,
+    pub sequence_index: usize,
+    /// Apples dropped by dead snakes, these don't count towards the target
+    pub dropped: HashSet<IVec2>,
 }
 
 #[derive(Copy, Clone, Event)]
@@ -28,46 +40,103 @@ pub enum AppleEv {
     Despawn(IVec2),
 }
 
+/// Where `AppleEv::SpawnRandom` places new apples
+#[derive(PartialEq, Clone)]
+pub enum AppleStrategy {
+    Random,
+    /// The free cell farthest from every snake head
+    Farthest,
+    /// Random, but cells get likelier the farther they are from the closest head
+    Weighted,
+    /// Cycles through a fixed list, skipping cells that are taken
+    Sequence(Vec<IVec2>),
+}
+
+/// Apples to keep on the board. When scaling with the live snakes there is
+/// always at least one.
+pub fn apple_target(apple_count: u32, apples_per_snake: Option<f32>, snakes: usize) -> usize {
+    match apples_per_snake {
+        Some(apples_per_snake) => ((snakes as f32 * apples_per_snake).ceil() as usize).max(1),
+        None => apple_count as usize,
+    }
+}
+
+/// Keeps the number of apples in line with the settings and, when scaling,
+/// the number of live snakes. This runs every frame, so a spawn that found no
+/// room is retried on the next.
+fn apple_target_system(
+    mut commands: Commands,
+    mut apples: ResMut<Apples>,
+    mut apple_ev: EventWriter<AppleEv>,
+    mut game_rng: ResMut<GameRng>,
+    snake_query: Query<&Snake>,
+    settings: Res<Settings>,
+) {
+    let target = apple_target(
+        settings.apple_count,
+        settings.apples_per_snake,
+        snake_query.iter().count(),
+    );
+    let mut counted: Vec<IVec2> = apples
+        .list
+        .keys()
+        .filter(|pos| !apples.dropped.contains(pos))
+        .copied()
+        .collect();
+
+    for _ in counted.len()..target {
+        apple_ev.send(AppleEv::SpawnRandom);
+    }
+
+    if counted.len() > target {
+        // hash map order isn't random, so pick which apples go explicitly
+        counted.sort_by_key(|pos| (pos.x, pos.y));
+        counted.shuffle(&mut game_rng.rng);
+        for pos in counted.drain(target..) {
+            if let Some(entity) = apples.list.remove(&pos) {
+                commands.entity(entity).despawn();
+            }
+        }
+    }
+}
+
 fn apple_system(
     mut commands: Commands,
     mut apples: ResMut<Apples>,
     walls: Res<Walls>,
     snake_query: Query<&Snake>,
     b: Res<Board>,
+    mut game_rng: ResMut<GameRng>,
     mut apple_ev: EventReader<AppleEv>,
     mut wall_ev: EventWriter<WallEv>,
+    colours: Res<Colours>,
     settings: Res<Settings>,
 ) {
-    let mut rng = rand::thread_rng();
+    let snakes: Vec<&Snake> = snake_query.iter().collect();
 
     for apple_ev in apple_ev.read() {
         match apple_ev {
             AppleEv::SpawnRandom | AppleEv::SpawnPos(_) => {
-                let mut count = 0;
-                let mut pos;
-                'apple: loop {
-                    pos = if let AppleEv::SpawnPos(set_pos) = apple_ev {
-                        *set_pos
-                    } else {
-                        IVec2::new(rng.gen_range(0..b.width), rng.gen_range(0..b.height))
-                    };
-
-                    count += 1;
-                    if count > 1000 {
-                        return;
-                    }
-
-                    if walls.list.contains_key(&pos) || apples.list.contains_key(&pos) {
-                        continue 'apple;
+                let pos = match apple_ev {
+                    AppleEv::SpawnPos(pos) => {
+                        is_legal_spawn(*pos, &b, &walls, &apples, &snakes).then_some(*pos)
                     }
+                    _ => pick_apple_cell(
+                        &settings.apple_strategy,
+                        &b,
+                        &walls,
+                        &mut apples,
+                        &snakes,
+                        &mut game_rng.rng,
+                    ),
+                };
 
-                    for snake in snake_query.iter() {
-                        if snake.body.contains(&pos) {
-                            continue 'apple;
-                        }
-                    }
+                let Some(pos) = pos else {
+                    continue;
+                };
 
-                    break 'apple;
+                if let AppleEv::SpawnPos(_) = apple_ev {
+                    apples.dropped.insert(pos);
                 }
 
                 let texture = apples.sprite.as_ref().unwrap().clone();
@@ -75,6 +144,10 @@ fn apple_system(
                     pos,
                     commands
                         .spawn(SpriteBundle {
+                            sprite: Sprite {
+                                color: apple_tint(colours.palette),
+                                ..default()
+                            },
                             texture: texture,
                             transform: Transform::from_xyz(
                                 pos.x as f32 - b.width as f32 / 2.0 + 0.5,
@@ -88,6 +161,7 @@ fn apple_system(
                 );
             }
             AppleEv::Despawn(pos) => {
+                apples.dropped.remove(pos);
                 if let Some(entity) = apples.list.remove(&pos) {
                     commands.entity(entity).despawn();
                     if settings.walls {
@@ -98,3 +172,360 @@ fn apple_system(
         }
     }
 }
+
+/// Something can spawn on `pos` if it's in bounds, not on a wall, apple or
+/// snake, and not in a wall trap, a cell with three or more sides closed off
+/// by walls or the edge of the board.
+pub fn is_legal_spawn(
+    pos: IVec2,
+    b: &Board,
+    walls: &Walls,
+    apples: &Apples,
+    snakes: &[&Snake],
+) -> bool {
+    let closed_sides = DIR
+        .into_iter()
+        .map(|dir| pos + IVec2::from(dir))
+        .filter(|side| !in_bounds(*side, b) || walls.list.contains_key(side))
+        .count();
+
+    in_bounds(pos, b)
+        && !walls.list.contains_key(&pos)
+        && !walls.moving.contains(&pos)
+        && !apples.list.contains_key(&pos)
+        && !snakes.iter().any(|snake| snake.body.contains(&pos))
+        && closed_sides < 3
+}
+
+/// Every cell that passes `is_legal_spawn`, so spawning never has to retry
+pub fn spawnable(b: &Board, walls: &Walls, apples: &Apples, snakes: &[&Snake]) -> Vec<IVec2> {
+    (0..b.width)
+        .flat_map(|x| (0..b.height).map(move |y| IVec2::new(x, y)))
+        .filter(|pos| is_legal_spawn(*pos, b, walls, apples, snakes))
+        .collect()
+}
+
+/// Where `strategy` puts the next apple. A strategy can come up empty, say
+/// with every head sealed in, so this falls back to any free cell rather
+/// than losing the apple.
+pub fn pick_apple_cell(
+    strategy: &AppleStrategy,
+    b: &Board,
+    walls: &Walls,
+    apples: &mut Apples,
+    snakes: &[&Snake],
+    rng: &mut impl Rng,
+) -> Option<IVec2> {
+    let pos = match strategy {
+        AppleStrategy::Random => None,
+        AppleStrategy::Farthest => farthest_cell(b, walls, apples, snakes),
+        AppleStrategy::Weighted => weighted_cell(b, walls, apples, snakes, rng),
+        AppleStrategy::Sequence(sequence) => {
+            let mut found = None;
+            for _ in 0..sequence.len() {
+                let pos = sequence[apples.sequence_index % sequence.len()];
+                apples.sequence_index += 1;
+                if is_legal_spawn(pos, b, walls, apples, snakes) {
+                    found = Some(pos);
+                    break;
+                }
+            }
+            found
+        }
+    };
+
+    pos.or_else(|| spawnable(b, walls, apples, snakes).choose(rng).copied())
+}
+
+/// A random free cell, weighted by its Manhattan distance to the closest head
+fn weighted_cell(
+    b: &Board,
+    walls: &Walls,
+    apples: &Apples,
+    snakes: &[&Snake],
+    rng: &mut impl Rng,
+) -> Option<IVec2> {
+    let heads: Vec<IVec2> = snakes
+        .iter()
+        .filter_map(|snake| snake.body.first().copied())
+        .collect();
+    spawnable(b, walls, apples, snakes)
+        .choose_weighted(rng, |pos| {
+            heads
+                .iter()
+                .map(|head| (*head - *pos).abs().element_sum())
+                .min()
+                .unwrap_or(1)
+        })
+        .ok()
+        .copied()
+}
+
+/// Breadth first search out from every snake head, returning the free cell
+/// with the largest distance.
+fn farthest_cell(b: &Board, walls: &Walls, apples: &Apples, snakes: &[&Snake]) -> Option<IVec2> {
+    let mut visited = HashSet::new();
+    let mut queue = VecDeque::new();
+    for snake in snakes {
+        if let Some(head) = snake.body.first() {
+            visited.insert(*head);
+            queue.push_back(*head);
+        }
+    }
+
+    let mut farthest = None;
+    while let Some(pos) = queue.pop_front() {
+        for dir in DIR {
+            let next = pos + IVec2::from(dir);
+            if !in_bounds(next, b)
+                || walls.list.contains_key(&next)
+                || snakes.iter().any(|snake| snake.body.contains(&next))
+                || !visited.insert(next)
+            {
+                continue;
+            }
+
+            if is_legal_spawn(next, b, walls, apples, snakes) {
+                farthest = Some(next);
+            }
+            queue.push_back(next);
+        }
+    }
+
+    farthest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn board(width: i32, height: i32) -> Board {
+        Board {
+            width,
+            height,
+            ..default()
+        }
+    }
+
+    fn snake(body: &[(i32, i32)]) -> Snake {
+        Snake {
+            body: body.iter().map(|&(x, y)| IVec2::new(x, y)).collect(),
+            ..default()
+        }
+    }
+
+    fn pick(
+        strategy: &AppleStrategy,
+        b: &Board,
+        apples: &mut Apples,
+        snakes: &[&Snake],
+    ) -> Option<IVec2> {
+        let mut rng = StdRng::seed_from_u64(0);
+        pick_apple_cell(strategy, b, &Walls::default(), apples, snakes, &mut rng)
+    }
+
+    #[test]
+    fn killing_a_snake_lowers_the_target() {
+        assert_eq!(apple_target(3, Some(1.0), 3), 3);
+        assert_eq!(apple_target(3, Some(1.0), 2), 2);
+    }
+
+    #[test]
+    fn target_rounds_up_and_keeps_one_apple() {
+        assert_eq!(apple_target(3, Some(0.5), 3), 2);
+        assert_eq!(apple_target(3, Some(0.5), 0), 1);
+    }
+
+    #[test]
+    fn target_follows_apple_count_when_not_scaling() {
+        assert_eq!(apple_target(5, None, 2), 5);
+        assert_eq!(apple_target(1, None, 0), 1);
+    }
+
+    #[test]
+    fn random_picks_the_only_free_cell() {
+        let b = board(3, 3);
+        let snake = snake(&[
+            (0, 0),
+            (1, 0),
+            (2, 0),
+            (2, 1),
+            (2, 2),
+            (1, 2),
+            (0, 2),
+            (0, 1),
+        ]);
+        let mut apples = Apples::default();
+        let pos = pick(&AppleStrategy::Random, &b, &mut apples, &[&snake]);
+        assert_eq!(pos, Some(IVec2::new(1, 1)));
+    }
+
+    #[test]
+    fn random_gives_up_on_a_full_board() {
+        let b = board(3, 1);
+        let snake = snake(&[(0, 0), (1, 0), (2, 0)]);
+        let mut apples = Apples::default();
+        let pos = pick(&AppleStrategy::Random, &b, &mut apples, &[&snake]);
+        assert_eq!(pos, None);
+    }
+
+    #[test]
+    fn farthest_picks_the_opposite_corner() {
+        let b = board(5, 5);
+        let snake = snake(&[(0, 0), (0, 1), (0, 2)]);
+        let mut apples = Apples::default();
+        let pos = pick(&AppleStrategy::Farthest, &b, &mut apples, &[&snake]);
+        assert_eq!(pos, Some(IVec2::new(4, 4)));
+    }
+
+    #[test]
+    fn farthest_skips_taken_cells() {
+        let b = board(5, 5);
+        let snake = snake(&[(0, 0), (0, 1), (0, 2)]);
+        let mut apples = Apples::default();
+        apples.list.insert(IVec2::new(4, 4), Entity::PLACEHOLDER);
+
+        let pos = pick(&AppleStrategy::Farthest, &b, &mut apples, &[&snake]).unwrap();
+        assert_eq!(pos.element_sum(), 7);
+    }
+
+    #[test]
+    fn farthest_skips_moving_walls() {
+        let b = board(5, 5);
+        let snake = snake(&[(0, 0), (0, 1), (0, 2)]);
+        let mut walls = Walls::default();
+        walls.moving.insert(IVec2::new(4, 4));
+        let mut rng = StdRng::seed_from_u64(0);
+
+        let pos = pick_apple_cell(
+            &AppleStrategy::Farthest,
+            &b,
+            &walls,
+            &mut Apples::default(),
+            &[&snake],
+            &mut rng,
+        )
+        .unwrap();
+        assert_ne!(pos, IVec2::new(4, 4));
+        assert_eq!(pos.element_sum(), 7);
+    }
+
+    #[test]
+    fn weighted_lands_farther_from_heads_than_random() {
+        let b = board(15, 15);
+        let snake = snake(&[(2, 2), (2, 1), (2, 0)]);
+        let mut rng = StdRng::seed_from_u64(1);
+
+        let mut mean_distance = |strategy: AppleStrategy| {
+            let mut apples = Apples::default();
+            let total: i32 = (0..1000)
+                .map(|_| {
+                    let pos = pick_apple_cell(
+                        &strategy,
+                        &b,
+                        &Walls::default(),
+                        &mut apples,
+                        &[&snake],
+                        &mut rng,
+                    )
+                    .unwrap();
+                    (pos - snake.body[0]).abs().element_sum()
+                })
+                .sum();
+            total as f32 / 1000.0
+        };
+
+        let random = mean_distance(AppleStrategy::Random);
+        let weighted = mean_distance(AppleStrategy::Weighted);
+        assert!(weighted > random, "weighted {weighted}, random {random}");
+    }
+
+    #[test]
+    fn sequence_cycles_and_skips_taken_cells() {
+        let b = board(5, 5);
+        let sequence =
+            AppleStrategy::Sequence(vec![IVec2::new(1, 1), IVec2::new(2, 2), IVec2::new(3, 3)]);
+        let mut apples = Apples::default();
+        apples.list.insert(IVec2::new(2, 2), Entity::PLACEHOLDER);
+
+        let first = pick(&sequence, &b, &mut apples, &[]).unwrap();
+        assert_eq!(first, IVec2::new(1, 1));
+        apples.list.insert(first, Entity::PLACEHOLDER);
+
+        assert_eq!(
+            pick(&sequence, &b, &mut apples, &[]),
+            Some(IVec2::new(3, 3))
+        );
+    }
+
+    #[test]
+    fn sequence_falls_back_to_a_free_cell() {
+        let b = board(2, 2);
+        let sequence = AppleStrategy::Sequence(vec![IVec2::new(0, 0), IVec2::new(5, 5)]);
+        let mut apples = Apples::default();
+        apples.list.insert(IVec2::new(0, 0), Entity::PLACEHOLDER);
+        let snake = snake(&[(0, 1), (1, 1)]);
+
+        assert_eq!(
+            pick(&sequence, &b, &mut apples, &[&snake]),
+            Some(IVec2::new(1, 0))
+        );
+    }
+
+    #[test]
+    fn spawns_are_rejected_on_taken_cells() {
+        let b = board(5, 5);
+        let snake = snake(&[(1, 1), (1, 2)]);
+        let mut walls = Walls::default();
+        walls.list.insert(IVec2::new(3, 3), Entity::PLACEHOLDER);
+        walls.moving.insert(IVec2::new(3, 1));
+        let mut apples = Apples::default();
+        apples.list.insert(IVec2::new(2, 2), Entity::PLACEHOLDER);
+
+        let legal = |pos| is_legal_spawn(IVec2::from(pos), &b, &walls, &apples, &[&snake]);
+        // snake, wall, moving wall and apple
+        for pos in [(1, 1), (1, 2), (3, 3), (3, 1), (2, 2)] {
+            assert!(!legal(pos), "{pos:?}");
+        }
+        assert!(legal((2, 3)));
+    }
+
+    #[test]
+    fn spawns_are_rejected_out_of_bounds() {
+        let b = board(5, 5);
+        let (walls, apples) = (Walls::default(), Apples::default());
+        for pos in [(-1, 2), (5, 2), (2, -1), (2, 5)] {
+            let pos = IVec2::from(pos);
+            assert!(!is_legal_spawn(pos, &b, &walls, &apples, &[]), "{pos}");
+        }
+    }
+
+    #[test]
+    fn nearly_full_board_only_offers_open_cells() {
+        let b = board(5, 5);
+        let mut walls = Walls::default();
+        for pos in [(1, 2), (3, 2), (2, 1)] {
+            walls.list.insert(IVec2::from(pos), Entity::PLACEHOLDER);
+        }
+        // everything else but the middle, walled in on three sides, and a corner
+        let body: Vec<(i32, i32)> = (0..5)
+            .flat_map(|x| (0..5).map(move |y| (x, y)))
+            .filter(|pos| ![(1, 2), (3, 2), (2, 1), (2, 2), (4, 4)].contains(pos))
+            .collect();
+        let snake = snake(&body);
+        let apples = Apples::default();
+
+        assert!(!is_legal_spawn(
+            IVec2::new(2, 2),
+            &b,
+            &walls,
+            &apples,
+            &[&snake]
+        ));
+        assert_eq!(
+            spawnable(&b, &walls, &apples, &[&snake]),
+            vec![IVec2::new(4, 4)]
+        );
+    }
+}
@@ -15,7 +15,6 @@ impl Plugin for EffectsPlugin {
 struct EffectsResources {
     boom_atlas_layout_handle: Handle<TextureAtlasLayout>,
     boom_texture_handle: Handle<Image>,
-    boom_sound_handle: Handle<AudioSource>,
 }
 
 fn setup(
@@ -34,13 +33,14 @@ fn setup(
     commands.insert_resource(EffectsResources {
         boom_atlas_layout_handle: boom_atlas_layout,
         boom_texture_handle: texture_handle,
-        boom_sound_handle: asset_server.load("sounds/boom.ogg"),
     });
 }
 
 #[derive(Event)]
 pub struct ExplosionEv {
     pub pos: IVec2,
+    /// Cells around `pos` caught in the blast, 0 only hits `pos`
+    pub radius: i32,
 }
 
 fn explosion_system(
@@ -53,6 +53,7 @@ fn explosion_system(
     time: Res<Time>,
 ) {
     for explosion in explosion_ev.read() {
+        let scale = 0.02 * (1.0 + explosion.radius as f32 * 0.5);
         commands.spawn((
             SpriteBundle {
                 texture: effect_resources.boom_texture_handle.clone(),
@@ -61,7 +62,7 @@ fn explosion_system(
                     explosion.pos.y as f32 - b.height as f32 / 2.0 + 0.5,
                     12.0,
                 )
-                .with_scale(Vec3::new(0.02, 0.02, 1.0)),
+                .with_scale(Vec3::new(scale, scale, 1.0)),
                 ..default()
             },
             TextureAtlas {
@@ -70,11 +71,6 @@ fn explosion_system(
             },
             AnimationTimer(Timer::from_seconds(0.04, TimerMode::Repeating)),
         ));
-
-        commands.spawn(AudioBundle {
-            source: effect_resources.boom_sound_handle.clone(),
-            ..default()
-        });
     }
 
     for (mut timer, mut texture_atlas, entity) in query.iter_mut() {
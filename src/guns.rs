@@ -38,10 +38,7 @@ pub fn bullet_spawner(
 
         for mut snake in snake_query.iter_mut() {
             if snake.id == bullet.id {
-                let len = snake.body.len();
-                if len > 0 {
-                    snake.body.remove(len - 1);
-                }
+                pay_for_shot(&mut snake);
             }
         }
 
@@ -61,11 +58,20 @@ pub fn bullet_spawner(
     }
 }
 
+/// Shooting costs a tail cell, but never takes the snake below a head and neck
+fn pay_for_shot(snake: &mut Snake) {
+    let len = snake.body.len();
+    if len > 2 {
+        snake.body.remove(len - 1);
+    }
+}
+
 pub fn bullet_system(
     mut commands: Commands,
-    mut snake_query: Query<&Snake>,
+    snake_query: Query<&Snake>,
     mut bullet_query: Query<(&mut Bullet, &mut Transform, Entity)>,
     time: Res<Time>,
+    keys: Res<ButtonInput<KeyCode>>,
     mut timer: ResMut<BulletTimer>,
     b: Res<Board>,
     settings: Res<Settings>,
@@ -78,48 +84,51 @@ pub fn bullet_system(
     timer
         .0
         .set_duration(Duration::from_secs_f32(1.0 / settings.tps));
-    timer.0.tick(time.delta());
+    tick_timer(&mut timer.0, &time, &keys, &settings);
 
-    'outer: for (mut bullet, mut transform, bullet_entity) in bullet_query.iter_mut() {
+    for (mut bullet, mut transform, bullet_entity) in bullet_query.iter_mut() {
         if timer.0.just_finished() {
-            // handle collision
-            for i in 0..=bullet.speed {
+            // find where the bullet hits something
+            let mut impact = None;
+            'step: for i in 0..=bullet.speed {
                 let pos = bullet.pos + bullet.dir * i as i32;
 
-                if !in_bounds(pos, &b) {
-                    explosion_ev.send(ExplosionEv { pos });
-                    commands.entity(bullet_entity).despawn();
-                    continue 'outer;
+                if !in_bounds(pos, &b) || walls.list.contains_key(&pos) {
+                    impact = Some(pos);
+                    break 'step;
                 }
 
-                for snake in snake_query.iter_mut() {
+                for snake in snake_query.iter() {
                     for j in 0..snake.body.len() {
-                        if snake.body[j] == pos {
-                            if j < 2 {
-                                if snake.id == bullet.id {
-                                    continue;
-                                }
-                            }
-
-                            commands.entity(bullet_entity).despawn();
-                            explosion_ev.send(ExplosionEv { pos });
-                            damage_ev.send(DamageSnakeEv {
-                                snake_id: snake.id,
-                                snake_pos: j,
-                            });
-
-                            continue 'outer;
+                        if snake.body[j] == pos && !(j < 2 && snake.id == bullet.id) {
+                            impact = Some(pos);
+                            break 'step;
                         }
                     }
                 }
+            }
+
+            if let Some(pos) = impact {
+                let radius = settings.explosion_radius;
+
+                commands.entity(bullet_entity).despawn();
+                explosion_ev.send(ExplosionEv { pos, radius });
+
+                let snakes: Vec<&Snake> = snake_query.iter().collect();
+                for (snake_id, snake_pos) in blast_hits(pos, radius, bullet.id, &snakes) {
+                    damage_ev.send(DamageSnakeEv {
+                        snake_id,
+                        snake_pos,
+                    });
+                }
 
-                for (wall_pos, _) in walls.list.iter() {
-                    if *wall_pos == pos {
-                        explosion_ev.send(ExplosionEv { pos });
-                        wall_ev.send(WallEv::Destroy(pos));
-                        commands.entity(bullet_entity).despawn();
+                for wall_pos in walls.list.keys() {
+                    if in_blast(*wall_pos, pos, radius) {
+                        wall_ev.send(WallEv::Destroy(*wall_pos));
                     }
                 }
+
+                continue;
             }
 
             let pos = bullet.pos + bullet.dir * bullet.speed as i32;
@@ -144,3 +153,76 @@ pub fn bullet_system(
         );
     }
 }
+
+/// Whether `cell` is within `radius` cells of a blast at `pos`, diagonals included
+fn in_blast(cell: IVec2, pos: IVec2, radius: i32) -> bool {
+    (cell - pos).abs().max_element() <= radius
+}
+
+/// The snakes caught in a blast, each cut at the first part inside it. The
+/// shooter's own head and neck are spared.
+fn blast_hits(pos: IVec2, radius: i32, shooter: u32, snakes: &[&Snake]) -> Vec<(u32, usize)> {
+    snakes
+        .iter()
+        .filter_map(|snake| {
+            (0..snake.body.len())
+                .find(|&j| in_blast(snake.body[j], pos, radius) && !(j < 2 && snake.id == shooter))
+                .map(|j| (snake.id, j))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shooting_stops_at_head_and_neck() {
+        let mut snake = Snake {
+            body: vec![IVec2::new(2, 0), IVec2::new(1, 0), IVec2::new(0, 0)],
+            ..default()
+        };
+
+        pay_for_shot(&mut snake);
+        assert_eq!(snake.body, vec![IVec2::new(2, 0), IVec2::new(1, 0)]);
+
+        pay_for_shot(&mut snake);
+        assert_eq!(snake.body.len(), 2);
+    }
+
+    fn snake(id: u32, body: &[(i32, i32)]) -> Snake {
+        Snake {
+            id,
+            body: body.iter().map(|&(x, y)| IVec2::new(x, y)).collect(),
+            ..default()
+        }
+    }
+
+    #[test]
+    fn radius_zero_only_hits_the_impact_cell() {
+        let other = snake(1, &[(3, 0), (2, 0), (1, 0)]);
+        assert_eq!(blast_hits(IVec2::new(2, 0), 0, 0, &[&other]), vec![(1, 1)]);
+        assert_eq!(blast_hits(IVec2::new(2, 1), 0, 0, &[&other]), vec![]);
+    }
+
+    #[test]
+    fn radius_one_hits_neighbours_and_diagonals() {
+        let other = snake(1, &[(4, 4), (3, 4), (3, 3)]);
+        let far = snake(2, &[(0, 4), (0, 3)]);
+        assert_eq!(
+            blast_hits(IVec2::new(2, 2), 1, 0, &[&other, &far]),
+            vec![(1, 2)]
+        );
+        assert!(in_blast(IVec2::new(1, 1), IVec2::new(2, 2), 1));
+        assert!(!in_blast(IVec2::new(0, 2), IVec2::new(2, 2), 1));
+    }
+
+    #[test]
+    fn blast_spares_the_shooters_head_and_neck() {
+        let shooter = snake(0, &[(2, 0), (1, 0), (0, 0)]);
+        assert_eq!(
+            blast_hits(IVec2::new(1, 0), 1, 0, &[&shooter]),
+            vec![(0, 2)]
+        );
+    }
+}
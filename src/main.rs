@@ -1,4 +1,4 @@
-use apples::{AppleEv, Apples};
+use apples::{AppleEv, AppleStrategy, Apples};
 use bevy::{
     prelude::*,
     render::{camera::ScalingMode, mesh::PrimitiveTopology},
@@ -7,16 +7,21 @@ use bevy::{
 use effects::ExplosionEv;
 use guns::{Bullet, SpawnBulletEv};
 use meshing::*;
-use rand::Rng;
-use snake::{DamageSnakeEv, InputMap, Snake};
-use std::collections::{HashMap, VecDeque};
-use walls::{WallEv, Walls};
+use rand::{rngs::StdRng, SeedableRng};
+use snake::{
+    Controls, DamageSnakeEv, InputMap, InputMode, InputRejectedEv, Points, Respawns, Snake,
+};
+use std::collections::{HashMap, HashSet, VecDeque};
+use walls::{spawn_wall, WallEv, Walls};
 
 mod apples;
 mod effects;
 mod guns;
 mod meshing;
 mod snake;
+mod snapshot;
+mod sounds;
+mod territory;
 mod ui;
 mod walls;
 
@@ -43,24 +48,98 @@ pub enum Speed {
     Fast,
 }
 
+/// What ends a multiplayer round and who wins it
+#[derive(PartialEq, Eq, Clone, Copy)]
+pub enum WinCondition {
+    LastStanding,
+    /// First snake to reach this many points wins the match, points reset after
+    FirstToScore(u32),
+    /// Plays until the time limit, most points wins
+    HighestAtTimeout,
+    /// First snake to grow this long wins
+    LongestSnake(u32),
+}
+
+/// Colour sets for the snakes and board
+#[derive(PartialEq, Eq, Clone, Copy)]
+pub enum Palette {
+    Default,
+    Deuteranopia,
+    Protanopia,
+    Tritanopia,
+}
+
+/// `board_size`, `snake_count`, `border`, `moving_walls` and `seed` take effect
+/// on the next round, everything else applies straight away
 #[derive(Resource)]
 pub struct Settings {
+    pub do_game_tick: bool,
     pub interpolation: bool,
+    pub interpolation_easing: bool,
+    pub head_interpolation: bool,
+    pub tail_interpolation: bool,
+    pub snake_style: SnakeStyle,
+    /// Empty cells kept between the board and the edge of the window
+    pub camera_margin: f32,
+    pub palette: Palette,
     pub tps: f32,
     pub tps_ramp: bool,
+    /// Speeds up as the snake grows, ignored while `tps_ramp` is on
+    pub length_speed: bool,
     pub snake_count: u32,
     pub apple_count: u32,
+    /// Overrides `apple_count` with this many apples per live snake
+    pub apples_per_snake: Option<f32>,
     pub board_size: BoardSize,
     pub walls: bool,
     pub walls_debug: bool,
+    /// Walls that patrol back and forth, only used when walls are on
+    pub moving_walls: u32,
+    /// Shades free cells by which snake can reach them first
+    pub territory_debug: bool,
+    /// Ticks a dead snake waits before respawning in multiplayer, `None` eliminates it.
+    pub respawn_delay: Option<u32>,
+    pub double_turn: bool,
+    pub apple_strategy: AppleStrategy,
+    pub show_input_feedback: bool,
+    pub input_mode: InputMode,
+    /// How many turns can be queued up ahead of the snake
+    pub input_buffer_depth: usize,
+    /// Lets a new turn replace the queue instead of being dropped. Feels
+    /// snappier at low tps, but a quick double tap can lose its first turn.
+    pub input_preempt: bool,
+    pub border: BorderSpec,
+    /// Cells around a bullet impact that also get hit
+    pub explosion_radius: i32,
+    /// Replays rounds with this seed instead of picking a new one
+    pub seed: Option<u64>,
+    /// Master volume for all sounds, 0 mutes
+    pub volume: f32,
+    /// Ends the round after this many seconds, respawn rounds default to
+    /// `RESPAWN_ROUND_TIME` when unset
+    pub time_limit: Option<f32>,
+    pub win_condition: WinCondition,
+    /// R restarts the round straight away, on by default in debug builds
+    pub debug_restart: bool,
 }
 
-#[derive(Resource)]
+/// Walls around the edge of the board, only used when walls are on
+#[derive(Clone, Copy)]
+pub struct BorderSpec {
+    pub thickness: i32,
+    /// Leaves a one cell gap in the middle of each side
+    pub gates: bool,
+}
+
+#[derive(Resource, Default)]
 pub struct Board {
     width: i32,
     height: i32,
     colour1: Color,
     colour2: Color,
+    /// Snakes the current round started with, `Settings::snake_count` can
+    /// change mid round
+    players: u32,
 }
 
 #[derive(Resource)]
@@ -69,16 +148,44 @@ pub struct MovmentTimer(Timer);
 pub struct BulletTimer(Timer);
 #[derive(Resource, Default)]
 pub struct GameTime(f32);
+/// Id of the snake that won the last round, `None` on a tie or in single player
+#[derive(Resource, Default)]
+pub struct Winner(Option<u32>);
 #[derive(Component, Deref, DerefMut)]
 pub struct AnimationTimer(Timer);
 
+/// Randomness for apples and walls, reseeded at the start of every round
+#[derive(Resource)]
+pub struct GameRng {
+    seed: u64,
+    rng: StdRng,
+}
+
+impl GameRng {
+    fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+}
+
 #[derive(Resource)]
 struct Colours {
     colours: Vec<Color>,
+    /// Palette `colours` was built from
+    palette: Palette,
 }
 
+pub const MAX_PLAYERS: usize = 8;
+
+// length of a multiplayer round when snakes respawn
+const RESPAWN_ROUND_TIME: f32 = 120.0;
+/// Main colour of the apple sprite
+const APPLE_RED: Color = Color::srgb(1.0, 0.27, 0.13);
+
 #[derive(Component)]
-struct BoardTile;
+struct BoardTile(IVec2);
 #[derive(Component)]
 struct MainCamera;
 
@@ -102,45 +209,79 @@ fn main() {
             walls::WallPlugin,
             guns::GunPlugin,
             apples::ApplePlugin,
+            snapshot::SnapshotPlugin,
+            sounds::SoundPlugin,
+            territory::TerritoryPlugin,
         ))
         .insert_resource(ClearColor(Color::srgb(0.1, 0.1, 0.1)))
         .insert_resource(Board {
             width: 10,
             height: 9,
-            colour1: Color::srgb(0.3, 0.5, 0.3),
-            colour2: Color::srgb(0.25, 0.45, 0.25),
+            colour1: board_colours(Palette::Default)[0],
+            colour2: board_colours(Palette::Default)[1],
+            players: 1,
         })
         .insert_resource(Settings {
+            do_game_tick: true,
             interpolation: true,
+            interpolation_easing: false,
+            head_interpolation: true,
+            tail_interpolation: true,
+            snake_style: SnakeStyle::Circles,
+            camera_margin: 0.0,
+            palette: Palette::Default,
             tps: 7.5,
             tps_ramp: false,
+            length_speed: false,
             snake_count: 1,
             apple_count: 3,
+            apples_per_snake: None,
             board_size: BoardSize::Medium,
             walls: false,
             walls_debug: false,
+            moving_walls: 0,
+            territory_debug: false,
+            respawn_delay: None,
+            double_turn: false,
+            apple_strategy: AppleStrategy::Random,
+            show_input_feedback: false,
+            input_mode: InputMode::Queue,
+            input_buffer_depth: 3,
+            input_preempt: false,
+            border: BorderSpec {
+                thickness: 0,
+                gates: false,
+            },
+            explosion_radius: 0,
+            seed: None,
+            volume: 1.0,
+            time_limit: None,
+            win_condition: WinCondition::LastStanding,
+            debug_restart: cfg!(debug_assertions),
         })
         .insert_resource(MovmentTimer(movment_timer.clone()))
         .insert_resource(BulletTimer(movment_timer))
         .insert_resource(GameTime::default())
+        .insert_resource(Winner::default())
+        .insert_resource(GameRng::new(rand::random()))
         .insert_resource(Apples {
             list: HashMap::new(),
             sprite: None,
+            sequence_index: 0,
+            dropped: HashSet::new(),
         })
         .insert_resource(Walls {
             list: HashMap::new(),
+            moving: HashSet::new(),
         })
         .insert_resource(Colours {
-            colours: vec![
-                Color::srgb(0.0, 0.7, 0.25),
-                Color::srgb(0.3, 0.4, 0.7),
-                Color::srgb(0.7, 0.4, 0.3),
-                Color::srgb(0.7, 0.7, 0.7),
-            ],
+            colours: snake_colours(Palette::Default),
+            palette: Palette::Default,
         })
         .init_state::<GameState>()
         .add_event::<ExplosionEv>()
         .add_event::<DamageSnakeEv>()
+        .add_event::<InputRejectedEv>()
         .add_event::<SpawnBulletEv>()
         .add_event::<AppleEv>()
         .add_event::<WallEv>()
@@ -148,32 +289,134 @@ fn main() {
         .add_systems(Update, game_state)
         .add_systems(OnEnter(GameState::Start), reset_game)
         .add_systems(Update, settings_system.run_if(in_state(GameState::InGame)))
+        .add_systems(Update, palette_system)
+        .add_systems(Update, camera_system.after(reset_game))
         .run();
 }
 
+#[allow(clippy::too_many_arguments)]
 fn game_state(
     game_state: Res<State<GameState>>,
     mut next_game_state: ResMut<NextState<GameState>>,
+    mut winner: ResMut<Winner>,
     keys: Res<ButtonInput<KeyCode>>,
     snake_query: Query<&Snake>,
     settings: Res<Settings>,
+    game_time: Res<GameTime>,
+    points: Res<Points>,
+    b: Res<Board>,
 ) {
     match game_state.get() {
         GameState::Setup => next_game_state.set(GameState::Start),
         GameState::Start => next_game_state.set(GameState::InGame),
         GameState::InGame => {
-            if snake_query.iter().count() <= (settings.snake_count != 1) as usize {
+            let snakes: Vec<&Snake> = snake_query.iter().collect();
+            let respawns = settings.respawn_delay.is_some();
+            if settings.debug_restart && keys.just_pressed(KeyCode::KeyR) {
+                winner.0 = None;
+                next_game_state.set(GameState::Start);
+            } else if is_game_over(
+                &snakes,
+                &points,
+                b.players,
+                settings.win_condition,
+                respawns,
+                settings.time_limit,
+                game_time.0,
+            ) {
+                winner.0 = round_winner(
+                    &snakes,
+                    &points,
+                    b.players,
+                    settings.win_condition,
+                    respawns,
+                );
                 next_game_state.set(GameState::GameOver);
             }
         }
         GameState::GameOver => {
             if keys.just_pressed(KeyCode::Space) {
+                winner.0 = None;
                 next_game_state.set(GameState::Start);
             }
         }
     }
 }
 
+/// Whether the round has ended, for every mode and win condition. `players`
+/// is how many snakes the round started with, not the current setting, so
+/// changing it mid round is safe.
+fn is_game_over(
+    snakes: &[&Snake],
+    points: &Points,
+    players: u32,
+    win_condition: WinCondition,
+    respawns: bool,
+    time_limit: Option<f32>,
+    game_time: f32,
+) -> bool {
+    let respawn_round = players != 1 && respawns;
+    let timed_round = respawn_round || win_condition == WinCondition::HighestAtTimeout;
+    let time_limit = time_limit.or(timed_round.then_some(RESPAWN_ROUND_TIME));
+
+    let alive = || snakes.iter().filter(|snake| !snake.dead);
+
+    let timed_out = time_limit.is_some_and(|limit| game_time >= limit);
+    // with nobody left to play against the round is over whatever the condition
+    let last_standing = !respawn_round && alive().count() <= (players != 1) as usize;
+    let won = match win_condition {
+        WinCondition::LastStanding | WinCondition::HighestAtTimeout => false,
+        WinCondition::FirstToScore(score) => points.points.iter().any(|points| *points >= score),
+        WinCondition::LongestSnake(length) => {
+            alive().any(|snake| snake.body.len() >= length as usize)
+        }
+    };
+
+    timed_out || last_standing || won
+}
+
+/// Who won the round that just ended, `None` on a tie or in single player
+fn round_winner(
+    snakes: &[&Snake],
+    points: &Points,
+    players: u32,
+    win_condition: WinCondition,
+    respawns: bool,
+) -> Option<u32> {
+    if players == 1 {
+        return None;
+    }
+
+    let scores: Vec<(u32, usize)> = match win_condition {
+        WinCondition::LastStanding if !respawns => {
+            snakes.iter().map(|snake| (snake.id, 1)).collect()
+        }
+        WinCondition::LongestSnake(_) => snakes
+            .iter()
+            .map(|snake| (snake.id, snake.body.len()))
+            .collect(),
+        // whoever completed the match wins it
+        WinCondition::FirstToScore(score) if points.points.iter().any(|p| *p >= score) => (0
+            ..players)
+            .map(|id| (id, (points.points[id as usize] >= score) as usize))
+            .collect(),
+        // points carry over between rounds, so only count this round's
+        _ => (0..players)
+            .map(|id| {
+                let gained = points.points[id as usize] - points.round_start[id as usize];
+                (id, gained as usize)
+            })
+            .collect(),
+    };
+
+    let best = scores.iter().map(|(_, score)| *score).max()?;
+    let mut leaders = scores.iter().filter(|(_, score)| *score == best);
+    match (leaders.next(), leaders.next()) {
+        (Some((id, _)), None) => Some(*id),
+        _ => None,
+    }
+}
+
 fn scene_setup(mut commands: Commands, mut apples: ResMut<Apples>, asset_server: Res<AssetServer>) {
     apples.sprite = Some(asset_server.load("images/apple.png"));
 
@@ -190,33 +433,63 @@ fn settings_system(
     mut settings: ResMut<Settings>,
     keys: Res<ButtonInput<KeyCode>>,
     mut game_time: ResMut<GameTime>,
+    snake_query: Query<&Snake>,
     time: Res<Time>,
 ) {
     if keys.just_pressed(KeyCode::KeyI) {
         settings.interpolation = !settings.interpolation;
     }
 
-    game_time.0 += time.delta_seconds();
+    if settings.do_game_tick {
+        game_time.0 += time.delta_seconds();
+    }
     if settings.tps_ramp {
         settings.tps = (game_time.0 * 0.1 + 5.0).clamp(5.0, 7.0);
+    } else if settings.length_speed {
+        // every snake shares one movement timer so go off the longest snake
+        let len = snake_query.iter().map(|snake| snake.body.len()).max();
+        if let Some(len) = len {
+            settings.tps = (5.0 + (len as f32 - 4.0) * 0.2).clamp(5.0, 12.0);
+        }
     }
 }
 
+fn camera_system(
+    mut camera_query: Query<&mut OrthographicProjection, With<MainCamera>>,
+    b: Res<Board>,
+    settings: Res<Settings>,
+) {
+    if !b.is_changed() && !settings.is_changed() {
+        return;
+    }
+
+    let mut camera_projection = camera_query.single_mut();
+    camera_projection.scaling_mode = ScalingMode::AutoMin {
+        min_height: b.height as f32 + settings.camera_margin * 2.0,
+        min_width: b.width as f32 + settings.camera_margin * 2.0,
+    };
+}
+
 fn reset_game(
     snake_query: Query<Entity, With<Snake>>,
     bullet_query: Query<Entity, With<Bullet>>,
     board_query: Query<Entity, With<BoardTile>>,
-    mut camera_query: Query<&mut OrthographicProjection, With<MainCamera>>,
     mut commands: Commands,
     mut apples: ResMut<Apples>,
     mut walls: ResMut<Walls>,
+    mut respawns: ResMut<Respawns>,
+    mut points: ResMut<Points>,
     mut game_time: ResMut<GameTime>,
     mut materials: ResMut<Assets<ColorMaterial>>,
     mut b: ResMut<Board>,
-    mut apple_ev: EventWriter<AppleEv>,
+    mut game_rng: ResMut<GameRng>,
     colours: Res<Colours>,
+    gamepads: Res<Gamepads>,
     settings: Res<Settings>,
 ) {
+    *game_rng = GameRng::new(settings.seed.unwrap_or_else(rand::random));
+    info!("Game seed: {}", game_rng.seed);
+
     for tile in board_query.iter() {
         commands.entity(tile).despawn();
     }
@@ -236,12 +509,6 @@ fn reset_game(
         }
     }
 
-    let mut camera_projection = camera_query.single_mut();
-    camera_projection.scaling_mode = ScalingMode::AutoMin {
-        min_height: b.height as f32,
-        min_width: b.width as f32,
-    };
-
     for x in 0..b.width {
         for y in 0..b.height {
             let color = if (x + y) % 2 == 0 {
@@ -260,7 +527,7 @@ fn reset_game(
                     ),
                     ..default()
                 },
-                BoardTile,
+                BoardTile(IVec2::new(x, y)),
             ));
         }
     }
@@ -276,67 +543,262 @@ fn reset_game(
         commands.entity(*apple.1).despawn();
     }
     apples.list = HashMap::new();
+    apples.sequence_index = 0;
+    apples.dropped.clear();
 
     for apple in walls.list.iter().clone() {
         commands.entity(*apple.1).despawn();
     }
     walls.list = HashMap::new();
 
-    for _ in 0..settings.apple_count {
-        apple_ev.send(AppleEv::SpawnRandom);
+    if settings.walls {
+        for pos in settings.border.cells(&b) {
+            walls.list.insert(pos, spawn_wall(&mut commands, &b, pos));
+        }
     }
 
+    respawns.list.clear();
+
+    b.players = settings.snake_count;
+
+    // a won match starts over from zero
+    if let WinCondition::FirstToScore(score) = settings.win_condition {
+        if points.points.iter().any(|points| *points >= score) {
+            points.points.fill(0);
+        }
+    }
+
+    // points carry over between rounds, only grow the list
+    if points.points.len() < settings.snake_count as usize {
+        points.points.resize(settings.snake_count as usize, 0);
+    }
+    points.round_start = points.points.clone();
+
+    // apple_target_system spawns the round's apples on the first frame
+
     game_time.0 = 0.0;
 
+    let transform = Transform::from_xyz(-b.width as f32 / 2.0, -b.height as f32 / 2.0, 0.0);
+
     // spawn in new snakes
-    let snake_controls = vec![
-        InputMap {
-            up: KeyCode::KeyW,
-            down: KeyCode::KeyS,
-            left: KeyCode::KeyA,
-            right: KeyCode::KeyD,
-            shoot: KeyCode::ShiftLeft,
-        },
-        InputMap {
-            up: KeyCode::ArrowUp,
-            down: KeyCode::ArrowDown,
-            left: KeyCode::ArrowLeft,
-            right: KeyCode::ArrowRight,
-            shoot: KeyCode::AltRight,
-        },
-        InputMap {
-            up: KeyCode::KeyP,
-            down: KeyCode::Semicolon,
-            left: KeyCode::KeyL,
-            right: KeyCode::Quote,
-            shoot: KeyCode::Backslash,
-        },
-        InputMap {
-            up: KeyCode::KeyY,
-            down: KeyCode::KeyH,
-            left: KeyCode::KeyG,
-            right: KeyCode::KeyJ,
-            shoot: KeyCode::KeyB,
-        },
-    ];
+    let positions = spawn_positions(&b, border_thickness(&settings, &b));
+    for (i, body) in positions
+        .iter()
+        .enumerate()
+        .take(settings.snake_count as usize)
+    {
+        commands.spawn((
+            MaterialMesh2dBundle {
+                material: materials.add(ColorMaterial::from(colours.colours[i])),
+                transform,
+                ..default()
+            },
+            Snake {
+                id: i as u32,
+                body: body.clone(),
+                controls: controls(i, &gamepads),
+                ..Default::default()
+            },
+        ));
+    }
+}
+
+const SNAKE_CONTROLS: [InputMap; 4] = [
+    InputMap {
+        up: KeyCode::KeyW,
+        down: KeyCode::KeyS,
+        left: KeyCode::KeyA,
+        right: KeyCode::KeyD,
+        shoot: KeyCode::ShiftLeft,
+    },
+    InputMap {
+        up: KeyCode::ArrowUp,
+        down: KeyCode::ArrowDown,
+        left: KeyCode::ArrowLeft,
+        right: KeyCode::ArrowRight,
+        shoot: KeyCode::AltRight,
+    },
+    InputMap {
+        up: KeyCode::KeyP,
+        down: KeyCode::Semicolon,
+        left: KeyCode::KeyL,
+        right: KeyCode::Quote,
+        shoot: KeyCode::Backslash,
+    },
+    InputMap {
+        up: KeyCode::KeyY,
+        down: KeyCode::KeyH,
+        left: KeyCode::KeyG,
+        right: KeyCode::KeyJ,
+        shoot: KeyCode::KeyB,
+    },
+];
+
+/// Keyboard controls for the first players, connected gamepads for the rest
+fn controls(id: usize, gamepads: &Gamepads) -> Controls {
+    if id < SNAKE_CONTROLS.len() {
+        return Controls::Keyboard(SNAKE_CONTROLS[id]);
+    }
+
+    // gamepad ids aren't reused after an unplug, so go by the connected ones
+    let mut connected: Vec<Gamepad> = gamepads.iter().collect();
+    connected.sort_by_key(|gamepad| gamepad.id);
+    match connected.get(id - SNAKE_CONTROLS.len()) {
+        Some(gamepad) => Controls::Gamepad(*gamepad),
+        None => Controls::None,
+    }
+}
+
+fn snake_colours(palette: Palette) -> Vec<Color> {
+    let mut colours = match palette {
+        Palette::Default => vec![
+            Color::srgb(0.0, 0.7, 0.25),
+            Color::srgb(0.3, 0.4, 0.7),
+            Color::srgb(0.7, 0.4, 0.3),
+            Color::srgb(0.7, 0.7, 0.7),
+        ],
+        // Okabe-Ito, leaving vermillion for the apple
+        Palette::Deuteranopia => vec![
+            Color::srgb(0.9, 0.62, 0.0),
+            Color::srgb(0.34, 0.71, 0.91),
+            Color::srgb(0.0, 0.62, 0.45),
+            Color::srgb(0.94, 0.89, 0.26),
+            Color::srgb(0.0, 0.45, 0.7),
+            Color::srgb(0.8, 0.47, 0.65),
+            Color::srgb(0.9, 0.9, 0.9),
+            Color::srgb(0.55, 0.55, 0.55),
+        ],
+        // reds look dark without red cones, so keep to blues and purples and
+        // leave the yellow end for the apple
+        Palette::Protanopia => vec![
+            Color::srgb(0.39, 0.56, 1.0),
+            Color::srgb(0.47, 0.37, 0.94),
+            Color::srgb(0.86, 0.15, 0.5),
+            Color::srgb(0.9, 0.9, 0.9),
+            Color::srgb(0.0, 0.75, 0.85),
+            Color::srgb(0.55, 0.55, 0.55),
+            Color::srgb(0.7, 0.8, 1.0),
+            Color::srgb(0.1, 0.25, 0.65),
+        ],
+        // teals and pinks stay apart without blue-yellow contrast, red is
+        // left for the apple
+        Palette::Tritanopia => vec![
+            Color::srgb(0.0, 0.6, 0.6),
+            Color::srgb(1.0, 0.6, 0.7),
+            Color::srgb(0.9, 0.9, 0.9),
+            Color::srgb(0.4, 0.85, 0.85),
+            Color::srgb(0.75, 0.2, 0.6),
+            Color::srgb(0.55, 0.55, 0.55),
+            Color::srgb(0.0, 0.4, 0.45),
+            Color::srgb(1.0, 0.85, 0.9),
+        ],
+    };
+
+    // spread the remaining players evenly around the hue wheel
+    let extra = MAX_PLAYERS.saturating_sub(colours.len());
+    for i in 0..extra {
+        let hue = 30.0 + 360.0 * i as f32 / extra as f32;
+        colours.push(Color::hsv(hue, 0.6, 0.75));
+    }
+
+    colours
+}
+
+/// What apples look like under `palette`
+fn apple_colour(palette: Palette) -> Color {
+    match palette {
+        Palette::Default => APPLE_RED,
+        Palette::Deuteranopia => Color::srgb(0.84, 0.37, 0.0),
+        Palette::Protanopia => Color::srgb(1.0, 0.69, 0.0),
+        Palette::Tritanopia => Color::srgb(0.89, 0.1, 0.11),
+    }
+}
+
+/// Sprite tint that turns the red apple sprite into `apple_colour`. Channels
+/// can go above 1 to pull them up from the red.
+fn apple_tint(palette: Palette) -> Color {
+    let target = apple_colour(palette).to_linear();
+    let red = APPLE_RED.to_linear();
+    Color::linear_rgb(
+        target.red / red.red,
+        target.green / red.green,
+        target.blue / red.blue,
+    )
+}
+
+fn board_colours(palette: Palette) -> [Color; 2] {
+    match palette {
+        Palette::Default => [Color::srgb(0.3, 0.5, 0.3), Color::srgb(0.25, 0.45, 0.25)],
+        // neutral tiles so no snake blends into the board
+        _ => [Color::srgb(0.3, 0.3, 0.34), Color::srgb(0.25, 0.25, 0.29)],
+    }
+}
+
+// recolours everything already on screen when the palette changes
+#[allow(clippy::too_many_arguments)]
+fn palette_system(
+    mut colours: ResMut<Colours>,
+    mut b: ResMut<Board>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    snake_query: Query<(&Snake, &Handle<ColorMaterial>)>,
+    mut tile_query: Query<(&BoardTile, &mut Sprite)>,
+    mut apple_query: Query<&mut Sprite, Without<BoardTile>>,
+    apples: Res<Apples>,
+    settings: Res<Settings>,
+) {
+    if colours.palette == settings.palette {
+        return;
+    }
+
+    colours.palette = settings.palette;
+    colours.colours = snake_colours(settings.palette);
+    [b.colour1, b.colour2] = board_colours(settings.palette);
+
+    for (snake, material) in snake_query.iter() {
+        if let Some(material) = materials.get_mut(material) {
+            material.color = colours.colours[snake.id as usize];
+        }
+    }
+
+    for (tile, mut sprite) in tile_query.iter_mut() {
+        sprite.color = if (tile.0.x + tile.0.y) % 2 == 0 {
+            b.colour1
+        } else {
+            b.colour2
+        };
+    }
+
+    for entity in apples.list.values() {
+        if let Ok(mut sprite) = apple_query.get_mut(*entity) {
+            sprite.color = apple_tint(settings.palette);
+        }
+    }
+}
+
+/// Starting bodies for each player, kept `inset` cells away from the edge
+fn spawn_positions(b: &Board, inset: i32) -> Vec<Vec<IVec2>> {
+    let width = b.width - inset * 2;
+    let height = b.height - inset * 2;
+    let offset = IVec2::splat(inset);
+
     let positions = vec![
         vec![
-            IVec2::new(4, b.height - 2),
-            IVec2::new(3, b.height - 2),
-            IVec2::new(2, b.height - 2),
-            IVec2::new(1, b.height - 2),
+            IVec2::new(4, height - 2),
+            IVec2::new(3, height - 2),
+            IVec2::new(2, height - 2),
+            IVec2::new(1, height - 2),
         ],
         vec![
-            IVec2::new(b.width - 5, 1),
-            IVec2::new(b.width - 4, 1),
-            IVec2::new(b.width - 3, 1),
-            IVec2::new(b.width - 2, 1),
+            IVec2::new(width - 5, 1),
+            IVec2::new(width - 4, 1),
+            IVec2::new(width - 3, 1),
+            IVec2::new(width - 2, 1),
         ],
         vec![
-            IVec2::new(b.width - 2, b.height - 5),
-            IVec2::new(b.width - 2, b.height - 4),
-            IVec2::new(b.width - 2, b.height - 3),
-            IVec2::new(b.width - 2, b.height - 2),
+            IVec2::new(width - 2, height - 5),
+            IVec2::new(width - 2, height - 4),
+            IVec2::new(width - 2, height - 3),
+            IVec2::new(width - 2, height - 2),
         ],
         vec![
             IVec2::new(1, 4),
@@ -344,24 +806,87 @@ fn reset_game(
             IVec2::new(1, 2),
             IVec2::new(1, 1),
         ],
+        // extra players start along the edges, going clockwise
+        vec![
+            IVec2::new(0, height / 2),
+            IVec2::new(0, height / 2 + 1),
+            IVec2::new(0, height / 2 + 2),
+            IVec2::new(0, height / 2 + 3),
+        ],
+        vec![
+            IVec2::new(width - 1, height / 2),
+            IVec2::new(width - 1, height / 2 - 1),
+            IVec2::new(width - 1, height / 2 - 2),
+            IVec2::new(width - 1, height / 2 - 3),
+        ],
+        vec![
+            IVec2::new(width / 2, height - 1),
+            IVec2::new(width / 2 + 1, height - 1),
+            IVec2::new(width / 2 + 2, height - 1),
+            IVec2::new(width / 2 + 3, height - 1),
+        ],
+        vec![
+            IVec2::new(width / 2, 0),
+            IVec2::new(width / 2 - 1, 0),
+            IVec2::new(width / 2 - 2, 0),
+            IVec2::new(width / 2 - 3, 0),
+        ],
     ];
 
-    let transform = Transform::from_xyz(-b.width as f32 / 2.0, -b.height as f32 / 2.0, 0.0);
+    positions
+        .into_iter()
+        .map(|body| body.into_iter().map(|pos| pos + offset).collect())
+        .collect()
+}
 
-    for i in 0..settings.snake_count as usize {
-        commands.spawn((
-            MaterialMesh2dBundle {
-                material: materials.add(ColorMaterial::from(colours.colours[i])),
-                transform,
-                ..default()
-            },
-            Snake {
-                id: i as u32,
-                body: positions[i].clone(),
-                input_map: snake_controls[i],
-                ..Default::default()
-            },
-        ));
+impl BorderSpec {
+    /// Thickness limited so the spawns still fit inside the border
+    fn thickness_on(&self, b: &Board) -> i32 {
+        self.thickness.min((b.height - 7) / 2).max(0)
+    }
+
+    /// Cells the border walls go on
+    fn cells(&self, b: &Board) -> Vec<IVec2> {
+        let thickness = self.thickness_on(b);
+        (0..b.width)
+            .flat_map(|x| (0..b.height).map(move |y| IVec2::new(x, y)))
+            .filter(|pos| {
+                let edge_distance = pos
+                    .x
+                    .min(pos.y)
+                    .min(b.width - 1 - pos.x)
+                    .min(b.height - 1 - pos.y);
+                let gate = self.gates && (pos.x == b.width / 2 || pos.y == b.height / 2);
+                edge_distance < thickness && !gate
+            })
+            .collect()
+    }
+}
+
+/// Thickness of the border walls, 0 when walls are off
+fn border_thickness(settings: &Settings, b: &Board) -> i32 {
+    if settings.walls {
+        settings.border.thickness_on(b)
+    } else {
+        0
+    }
+}
+
+/// Advances a game tick timer, or while paused only steps it when period is pressed
+pub fn tick_timer(
+    timer: &mut Timer,
+    time: &Time,
+    keys: &ButtonInput<KeyCode>,
+    settings: &Settings,
+) {
+    if settings.do_game_tick {
+        timer.tick(time.delta());
+    } else if keys.just_pressed(KeyCode::Period) {
+        let remaining = timer.remaining();
+        timer.tick(remaining);
+    } else {
+        // still tick so just_finished gets cleared
+        timer.tick(std::time::Duration::ZERO);
     }
 }
 
@@ -378,3 +903,194 @@ fn calculate_flip(dir: IVec2) -> IVec2 {
         _ => IVec2::new(1, 1),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spawns_never_land_on_border_walls() {
+        for (width, height) in [(10, 9), (17, 15), (24, 21)] {
+            let b = Board {
+                width,
+                height,
+                ..default()
+            };
+
+            for thickness in 1..=3 {
+                for gates in [false, true] {
+                    let border = BorderSpec { thickness, gates };
+                    let walls = border.cells(&b);
+                    assert!(!walls.is_empty());
+
+                    for pos in spawn_positions(&b, border.thickness_on(&b)).concat() {
+                        assert!(in_bounds(pos, &b));
+                        assert!(!walls.contains(&pos), "{pos} is a border wall");
+                    }
+                }
+            }
+        }
+    }
+
+    fn snake(id: u32, len: i32) -> Snake {
+        Snake {
+            id,
+            body: (0..len).map(|x| IVec2::new(x, id as i32)).collect(),
+            ..default()
+        }
+    }
+
+    fn points(points: &[u32], round_start: &[u32]) -> Points {
+        Points {
+            points: points.to_vec(),
+            round_start: round_start.to_vec(),
+        }
+    }
+
+    /// `is_game_over` for a round without respawns or a time limit setting
+    fn over(
+        snakes: &[&Snake],
+        points: &Points,
+        players: u32,
+        win: WinCondition,
+        time: f32,
+    ) -> bool {
+        is_game_over(snakes, points, players, win, false, None, time)
+    }
+
+    #[test]
+    fn raising_the_player_count_doesnt_end_a_solo_round() {
+        // the round started alone, the slider has since been moved to two
+        let a = snake(0, 3);
+        let p = points(&[0, 0], &[0, 0]);
+        assert!(!over(&[&a], &p, 1, WinCondition::LastStanding, 0.0));
+        assert!(over(&[], &p, 1, WinCondition::LastStanding, 0.0));
+    }
+
+    #[test]
+    fn lowering_the_player_count_doesnt_end_a_multiplayer_round() {
+        let (a, b) = (snake(0, 3), snake(1, 3));
+        let p = points(&[0], &[0]);
+        assert!(!over(&[&a, &b], &p, 2, WinCondition::LastStanding, 0.0));
+        assert!(over(&[&a], &p, 2, WinCondition::LastStanding, 0.0));
+    }
+
+    #[test]
+    fn last_standing_wins() {
+        let win = WinCondition::LastStanding;
+        let (a, b) = (snake(0, 3), snake(1, 3));
+        let p = points(&[0, 0], &[0, 0]);
+        assert!(!over(&[&a, &b], &p, 2, win, 0.0));
+        assert!(over(&[&b], &p, 2, win, 0.0));
+        assert_eq!(round_winner(&[&b], &p, 2, win, false), Some(1));
+        // both dying on the same tick is a tie
+        assert_eq!(round_winner(&[], &p, 2, win, false), None);
+    }
+
+    #[test]
+    fn first_to_score_wins() {
+        let win = WinCondition::FirstToScore(5);
+        let (a, b) = (snake(0, 3), snake(1, 3));
+        let p = points(&[4, 2], &[0, 0]);
+        assert!(!over(&[&a, &b], &p, 2, win, 0.0));
+
+        let p = points(&[5, 2], &[4, 0]);
+        assert!(over(&[&a, &b], &p, 2, win, 0.0));
+        assert_eq!(round_winner(&[&a, &b], &p, 2, win, false), Some(0));
+
+        let p = points(&[5, 5], &[4, 4]);
+        assert_eq!(round_winner(&[&a, &b], &p, 2, win, false), None);
+    }
+
+    #[test]
+    fn highest_at_timeout_wins_on_points_this_round() {
+        let win = WinCondition::HighestAtTimeout;
+        let (a, b) = (snake(0, 3), snake(1, 3));
+        // snake 0 leads overall but snake 1 scored more this round
+        let p = points(&[6, 3], &[5, 0]);
+        assert!(!over(&[&a, &b], &p, 2, win, RESPAWN_ROUND_TIME - 1.0));
+        assert!(over(&[&a, &b], &p, 2, win, RESPAWN_ROUND_TIME));
+        assert_eq!(round_winner(&[&a, &b], &p, 2, win, false), Some(1));
+
+        let p = points(&[7, 2], &[5, 0]);
+        assert_eq!(round_winner(&[&a, &b], &p, 2, win, false), None);
+    }
+
+    #[test]
+    fn longest_snake_wins() {
+        let win = WinCondition::LongestSnake(5);
+        let p = points(&[0, 0], &[0, 0]);
+        let (a, b) = (snake(0, 4), snake(1, 3));
+        assert!(!over(&[&a, &b], &p, 2, win, 0.0));
+
+        let (a, b) = (snake(0, 5), snake(1, 3));
+        assert!(over(&[&a, &b], &p, 2, win, 0.0));
+        assert_eq!(round_winner(&[&a, &b], &p, 2, win, false), Some(0));
+
+        let (a, b) = (snake(0, 5), snake(1, 5));
+        assert_eq!(round_winner(&[&a, &b], &p, 2, win, false), None);
+    }
+
+    #[test]
+    fn single_player_has_no_winner() {
+        let a = snake(0, 3);
+        let p = points(&[3], &[0]);
+        assert_eq!(
+            round_winner(&[&a], &p, 1, WinCondition::LastStanding, false),
+            None
+        );
+    }
+
+    #[test]
+    fn palettes_keep_snakes_apart_from_the_apple() {
+        let palettes = [
+            Palette::Default,
+            Palette::Deuteranopia,
+            Palette::Protanopia,
+            Palette::Tritanopia,
+        ];
+        for palette in palettes {
+            let colours = snake_colours(palette);
+            assert_eq!(colours.len(), MAX_PLAYERS);
+            assert!(!colours.contains(&apple_colour(palette)));
+            for (i, colour) in colours.iter().enumerate() {
+                assert!(!colours[i + 1..].contains(colour));
+            }
+        }
+
+        assert_ne!(
+            snake_colours(Palette::Deuteranopia),
+            snake_colours(Palette::Protanopia)
+        );
+        assert_eq!(
+            apple_tint(Palette::Default),
+            Color::linear_rgb(1.0, 1.0, 1.0)
+        );
+    }
+
+    #[test]
+    fn single_player_ends_when_the_snake_dies() {
+        let mut a = snake(0, 3);
+        let p = points(&[0], &[0]);
+        assert!(!over(&[&a], &p, 1, WinCondition::LastStanding, 0.0));
+
+        // the dead body stays on screen but no longer counts
+        a.dead = true;
+        assert!(over(&[&a], &p, 1, WinCondition::LastStanding, 0.0));
+    }
+
+    #[test]
+    fn multiplayer_ends_with_one_snake_left() {
+        let (a, b, c) = (snake(0, 3), snake(1, 3), snake(2, 3));
+        let p = points(&[0, 0, 0], &[0, 0, 0]);
+        assert!(!over(&[&a, &b, &c], &p, 3, WinCondition::LastStanding, 0.0));
+        assert!(!over(&[&a, &c], &p, 3, WinCondition::LastStanding, 0.0));
+        assert!(over(&[&c], &p, 3, WinCondition::LastStanding, 0.0));
+
+        // respawning rounds only end on time
+        let respawning =
+            |time| is_game_over(&[&c], &p, 3, WinCondition::LastStanding, true, None, time);
+        assert!(!respawning(0.0));
+        assert!(respawning(RESPAWN_ROUND_TIME));
+    }
+}
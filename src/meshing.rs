@@ -2,6 +2,17 @@ use bevy::render::render_asset::RenderAssetUsages;
 
 use super::*;
 
+/// How snake bodies are drawn
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum SnakeStyle {
+    /// Round joints on every cell with a slightly bigger head
+    Circles,
+    /// Smooth tube that only rounds off the corners
+    Capsule,
+    /// Square cells for a retro look
+    Blocky,
+}
+
 struct TmpMesh {
     verticies: Vec<[f32; 3]>,
 }
@@ -62,6 +73,13 @@ impl TmpMesh {
             last = Vec2::new(x, y);
         }
     }
+
+    fn push_joint(&mut self, style: SnakeStyle, pos: IVec2, offset: Vec2, radius: f32) {
+        match style {
+            SnakeStyle::Blocky => self.push_quad(pos, offset, Vec2::splat(radius), IVec2::X),
+            SnakeStyle::Circles | SnakeStyle::Capsule => self.push_circle(pos, offset, radius),
+        }
+    }
 }
 
 impl From<TmpMesh> for Mesh {
@@ -85,11 +103,33 @@ impl From<TmpMesh> for Mesh {
     }
 }
 
-pub fn mesh_snake(snake: &Snake, interpolation: f32) -> Mesh {
+/// Smoothstep easing of an interpolation value in the range -0.5..0.5
+pub fn ease_in_out(interpolation: f32) -> f32 {
+    let t = (interpolation + 0.5).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t) - 0.5
+}
+
+pub fn mesh_snake(
+    snake: &Snake,
+    head_interpolation: f32,
+    tail_interpolation: f32,
+    style: SnakeStyle,
+) -> Mesh {
     let mut tmp_mesh = TmpMesh::new();
 
-    let width = 0.6;
-    let head_size = 0.7;
+    let (width, head_size) = match style {
+        SnakeStyle::Circles => (0.6, 0.7),
+        SnakeStyle::Capsule => (0.6, 0.6),
+        SnakeStyle::Blocky => (0.9, 0.9),
+    };
+
+    // a snake that has been cut down to its head can't be interpolated
+    if snake.body.len() < 2 {
+        if let Some(head) = snake.body.first() {
+            tmp_mesh.push_joint(style, *head, Vec2::ZERO, head_size / 2.0);
+        }
+        return tmp_mesh.into();
+    }
 
     let head = snake.body[0];
     let neck = snake.body[1];
@@ -99,6 +139,7 @@ pub fn mesh_snake(snake: &Snake, interpolation: f32) -> Mesh {
     let mut start = 1;
     let mut end = len - 1;
 
+    let interpolation = head_interpolation;
     if interpolation >= 0.0 {
         start = 0;
 
@@ -109,24 +150,13 @@ pub fn mesh_snake(snake: &Snake, interpolation: f32) -> Mesh {
             Vec2::new(width / 2.0, interpolation / 2.0),
             calculate_flip(snake.head_dir),
         );
-        tmp_mesh.push_circle(
+        tmp_mesh.push_joint(
+            style,
             head,
             snake.head_dir.as_vec2() * interpolation,
             head_size / 2.0,
         );
-
-        // Interpolate tail
-        let tail_dir = snake.body[len - 2] - snake.body[len - 1];
-        tmp_mesh.push_quad(
-            tail,
-            Vec2::new(0.0, interpolation / 2.0 + 0.25),
-            Vec2::new(width / 2.0, -interpolation / 2.0 + 0.25),
-            calculate_flip(tail_dir),
-        );
-        tmp_mesh.push_circle(tail, tail_dir.as_vec2() * interpolation, width / 2.0);
     } else {
-        end = len;
-
         // Interpolate head
         tmp_mesh.push_quad(
             head,
@@ -134,11 +164,27 @@ pub fn mesh_snake(snake: &Snake, interpolation: f32) -> Mesh {
             Vec2::new(width / 2.0, interpolation / 2.0 + 0.25),
             calculate_flip(head - neck),
         );
-        tmp_mesh.push_circle(
+        tmp_mesh.push_joint(
+            style,
             head,
             (head - neck).as_vec2() * interpolation,
             head_size / 2.0,
         );
+    }
+
+    let interpolation = tail_interpolation;
+    if interpolation >= 0.0 {
+        // Interpolate tail
+        let tail_dir = snake.body[len - 2] - snake.body[len - 1];
+        tmp_mesh.push_quad(
+            tail,
+            Vec2::new(0.0, interpolation / 2.0 + 0.25),
+            Vec2::new(width / 2.0, -interpolation / 2.0 + 0.25),
+            calculate_flip(tail_dir),
+        );
+        tmp_mesh.push_joint(style, tail, tail_dir.as_vec2() * interpolation, width / 2.0);
+    } else {
+        end = len;
 
         // Interpolate tail
         tmp_mesh.push_quad(
@@ -147,14 +193,23 @@ pub fn mesh_snake(snake: &Snake, interpolation: f32) -> Mesh {
             Vec2::new(width / 2.0, -interpolation / 2.0),
             calculate_flip(snake.tail_dir),
         );
-        tmp_mesh.push_circle(tail, snake.tail_dir.as_vec2() * interpolation, width / 2.0);
+        tmp_mesh.push_joint(
+            style,
+            tail,
+            snake.tail_dir.as_vec2() * interpolation,
+            width / 2.0,
+        );
     }
 
     let mut last = head;
     for i in start..end {
         let pos = snake.body[i];
 
-        tmp_mesh.push_circle(pos, Vec2::new(0.0, 0.0), width / 2.0);
+        // straight runs of a capsule are already covered by the quads
+        let straight = i > 0 && i < len - 1 && last - pos == pos - snake.body[i + 1];
+        if style != SnakeStyle::Capsule || !straight {
+            tmp_mesh.push_joint(style, pos, Vec2::new(0.0, 0.0), width / 2.0);
+        }
 
         if i > 0 {
             let flip1 = calculate_flip(last - pos);
@@ -182,3 +237,27 @@ pub fn mesh_snake(snake: &Snake, interpolation: f32) -> Mesh {
 
     tmp_mesh.into()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn short_snakes_mesh_without_panicking() {
+        for len in 1..=3 {
+            let snake = Snake {
+                body: (0..len).rev().map(|x| IVec2::new(x, 0)).collect(),
+                head_dir: IVec2::X,
+                tail_dir: IVec2::X,
+                ..default()
+            };
+
+            for style in [SnakeStyle::Circles, SnakeStyle::Capsule, SnakeStyle::Blocky] {
+                for interpolation in [-0.5, 0.0, 0.5] {
+                    let mesh = mesh_snake(&snake, interpolation, interpolation, style);
+                    assert!(mesh.count_vertices() > 0, "length {len}");
+                }
+            }
+        }
+    }
+}
@@ -4,7 +4,12 @@ pub struct SnakePlugin;
 
 impl Plugin for SnakePlugin {
     fn build(&self, app: &mut App) {
-        app.insert_resource(Points { points: [0; 4] }).add_systems(
+        app.insert_resource(Points {
+            points: Vec::new(),
+            round_start: Vec::new(),
+        })
+        .insert_resource(Respawns { list: Vec::new() })
+        .add_systems(
             Update,
             (
                 damage_snake_system
@@ -12,6 +17,9 @@ impl Plugin for SnakePlugin {
                     .after(guns::bullet_system)
                     .before(game_state),
                 snake_system.run_if(in_state(GameState::InGame)),
+                respawn_system
+                    .run_if(in_state(GameState::InGame))
+                    .after(damage_snake_system),
             ),
         );
     }
@@ -21,10 +29,16 @@ impl Plugin for SnakePlugin {
 pub struct Snake {
     pub id: u32,
     pub body: Vec<IVec2>,
-    pub input_map: InputMap,
-    pub input_queue: VecDeque<Direction>,
+    pub controls: Controls,
+    pub input_queue: InputQueue,
     pub head_dir: IVec2,
     pub tail_dir: IVec2,
+    /// Cells moved on the last tick, 2 when a double turn was taken
+    pub steps: usize,
+    /// Killed in single player, where the body stays on screen for game over
+    pub dead: bool,
+    /// Direction the gamepad stick was held in last frame
+    pub held_stick: Option<Direction>,
 }
 
 impl Default for Snake {
@@ -32,20 +46,69 @@ impl Default for Snake {
         Snake {
             id: 0,
             body: Vec::new(),
-            input_map: InputMap {
+            controls: Controls::Keyboard(InputMap {
                 up: KeyCode::KeyW,
                 down: KeyCode::KeyS,
                 left: KeyCode::KeyA,
                 right: KeyCode::KeyD,
                 shoot: KeyCode::KeyR,
-            },
-            input_queue: VecDeque::new(),
+            }),
+            input_queue: InputQueue::default(),
             head_dir: IVec2::new(0, 0),
             tail_dir: IVec2::new(0, 0),
+            steps: 1,
+            dead: false,
+            held_stick: None,
         }
     }
 }
 
+impl Snake {
+    /// Cells to move this tick, with double turn two queued turns are taken
+    /// in one tick
+    pub fn steps_this_tick(&self, double_turn: bool) -> usize {
+        if double_turn && self.input_queue.len() >= 2 {
+            2
+        } else {
+            1
+        }
+    }
+
+    /// Moves the head one cell, taking the next queued turn if there is one.
+    /// The tail follows unless `eats` says there's an apple at the new head.
+    pub fn step(&mut self, eats: impl Fn(IVec2) -> bool) -> IVec2 {
+        let head = self.body[0];
+        let current_dir = head - self.body[1];
+        let new_head = if let Some(direction) = self.input_queue.pop_front() {
+            let dir: IVec2 = DIR[direction as usize].into();
+            head + dir
+        } else {
+            head + current_dir
+        };
+
+        self.body.insert(0, new_head);
+
+        if !eats(new_head) {
+            let len = self.body.len();
+            self.tail_dir = self.body[len - 2] - self.body[len - 1];
+
+            // Shrink Snake
+            self.body.remove(len - 1);
+        }
+
+        new_head
+    }
+
+    /// Turns the stick direction held this frame into a press. A held stick
+    /// only counts once, it has to change direction or go back to neutral
+    /// before it turns the snake again.
+    pub fn stick_press(&mut self, stick: Option<Direction>) -> Option<Direction> {
+        let pressed = stick.filter(|dir| self.held_stick != Some(*dir));
+        self.held_stick = stick;
+        pressed
+    }
+}
+
 #[derive(Clone, Copy)]
 pub struct InputMap {
     pub up: KeyCode,
@@ -55,6 +118,82 @@ pub struct InputMap {
     pub shoot: KeyCode,
 }
 
+#[derive(Clone, Copy)]
+pub enum Controls {
+    Keyboard(InputMap),
+    Gamepad(Gamepad),
+    /// Not enough gamepads were connected for this player
+    None,
+}
+
+impl Controls {
+    /// Returns true if the button for `dir` was pressed this frame
+    pub fn just_pressed(
+        &self,
+        dir: Direction,
+        keys: &ButtonInput<KeyCode>,
+        buttons: &ButtonInput<GamepadButton>,
+    ) -> bool {
+        match self {
+            Controls::Keyboard(input_map) => keys.just_pressed(match dir {
+                Direction::Up => input_map.up,
+                Direction::Down => input_map.down,
+                Direction::Left => input_map.left,
+                Direction::Right => input_map.right,
+            }),
+            Controls::Gamepad(gamepad) => buttons.just_pressed(GamepadButton::new(
+                *gamepad,
+                match dir {
+                    Direction::Up => GamepadButtonType::DPadUp,
+                    Direction::Down => GamepadButtonType::DPadDown,
+                    Direction::Left => GamepadButtonType::DPadLeft,
+                    Direction::Right => GamepadButtonType::DPadRight,
+                },
+            )),
+            Controls::None => false,
+        }
+    }
+
+    /// Direction the left stick is pushed in, if it's far enough from neutral
+    pub fn stick_direction(&self, axes: &Axis<GamepadAxis>) -> Option<Direction> {
+        let Controls::Gamepad(gamepad) = self else {
+            return None;
+        };
+
+        let x = axes.get(GamepadAxis::new(*gamepad, GamepadAxisType::LeftStickX))?;
+        let y = axes.get(GamepadAxis::new(*gamepad, GamepadAxisType::LeftStickY))?;
+        if x.abs().max(y.abs()) < 0.5 {
+            None
+        } else if x.abs() > y.abs() {
+            Some(if x > 0.0 {
+                Direction::Right
+            } else {
+                Direction::Left
+            })
+        } else {
+            Some(if y > 0.0 {
+                Direction::Up
+            } else {
+                Direction::Down
+            })
+        }
+    }
+
+    pub fn shoot_pressed(
+        &self,
+        keys: &ButtonInput<KeyCode>,
+        buttons: &ButtonInput<GamepadButton>,
+    ) -> bool {
+        match self {
+            Controls::Keyboard(input_map) => keys.just_pressed(input_map.shoot),
+            Controls::Gamepad(gamepad) => {
+                buttons.just_pressed(GamepadButton::new(*gamepad, GamepadButtonType::South))
+            }
+            Controls::None => false,
+        }
+    }
+}
+
 pub fn snake_system(
     mut snake_query: Query<(&mut Snake, &mut Mesh2dHandle)>,
     mut meshes: ResMut<Assets<Mesh>>,
@@ -62,8 +201,11 @@ pub fn snake_system(
     mut damage_ev: EventWriter<DamageSnakeEv>,
     mut spawn_bullet_ev: EventWriter<SpawnBulletEv>,
     mut apple_ev: EventWriter<AppleEv>,
+    mut input_rejected_ev: EventWriter<InputRejectedEv>,
     time: Res<Time>,
     keys: Res<ButtonInput<KeyCode>>,
+    buttons: Res<ButtonInput<GamepadButton>>,
+    axes: Res<Axis<GamepadAxis>>,
     apples: Res<Apples>,
     walls: Res<Walls>,
     b: Res<Board>,
@@ -72,37 +214,56 @@ pub fn snake_system(
     timer
         .0
         .set_duration(std::time::Duration::from_secs_f32(1.0 / settings.tps));
-    timer.0.tick(time.delta());
+    tick_timer(&mut timer.0, &time, &keys, &settings);
 
     for (mut snake, mut mesh_handle) in snake_query.iter_mut() {
+        // snakes this short are about to be despawned by damage_snake_system
+        if snake.body.len() < 2 {
+            update_mesh(
+                &mut meshes,
+                &mut mesh_handle,
+                mesh_snake(&snake, 0.0, 0.0, settings.snake_style),
+            );
+            continue;
+        }
+
         let head = snake.body[0];
         let neck = snake.body[1];
         let current_dir = head - neck;
         let forward = head - neck;
 
-        let last_in_queue = *snake.input_queue.back().unwrap_or(&get_direction(forward));
-        if snake.input_queue.len() < 3 {
-            if keys.just_pressed(snake.input_map.up) {
-                if last_in_queue != Direction::Down && last_in_queue != Direction::Up {
-                    snake.input_queue.push_back(Direction::Up);
-                }
-            } else if keys.just_pressed(snake.input_map.down) {
-                if last_in_queue != Direction::Up && last_in_queue != Direction::Down {
-                    snake.input_queue.push_back(Direction::Down);
-                }
-            } else if keys.just_pressed(snake.input_map.left) {
-                if last_in_queue != Direction::Right && last_in_queue != Direction::Left {
-                    snake.input_queue.push_back(Direction::Left);
-                }
-            } else if keys.just_pressed(snake.input_map.right) {
-                if last_in_queue != Direction::Left && last_in_queue != Direction::Right {
-                    snake.input_queue.push_back(Direction::Right);
-                }
+        let pressed = [
+            Direction::Up,
+            Direction::Down,
+            Direction::Left,
+            Direction::Right,
+        ]
+        .into_iter()
+        .find(|dir| snake.controls.just_pressed(*dir, &keys, &buttons));
+
+        let stick = snake.controls.stick_direction(&axes);
+        let pressed = pressed.or(snake.stick_press(stick));
+
+        if let Some(dir) = pressed {
+            let result = match settings.input_mode {
+                InputMode::Queue => snake.input_queue.push(
+                    dir,
+                    get_direction(forward),
+                    settings.input_buffer_depth,
+                    settings.input_preempt,
+                ),
+                InputMode::LatestWins => snake.input_queue.replace(dir, get_direction(forward)),
+            };
+            if let Err(reason) = result {
+                input_rejected_ev.send(InputRejectedEv {
+                    snake_id: snake.id,
+                    reason,
+                });
             }
         }
 
         let len = snake.body.len();
-        if keys.just_pressed(snake.input_map.shoot) && len > 2 {
+        if snake.controls.shoot_pressed(&keys, &buttons) && len > 2 {
             spawn_bullet_ev.send(SpawnBulletEv(Bullet {
                 id: snake.id,
                 pos: head,
@@ -112,32 +273,21 @@ pub fn snake_system(
         }
 
         if timer.0.just_finished() {
-            let new_head = if let Some(direction) = snake.input_queue.pop_front() {
-                let dir: IVec2 = DIR[direction as usize].into();
-                head + dir
-            } else {
-                head + current_dir
-            };
-
-            snake.body.insert(0, new_head);
+            snake.steps = snake.steps_this_tick(settings.double_turn);
 
-            let head = snake.body[0];
-            if walls.list.contains_key(&head) {
-                damage_ev.send(DamageSnakeEv {
-                    snake_id: snake.id,
-                    snake_pos: 0,
-                });
-            }
+            for _ in 0..snake.steps {
+                let head = snake.step(|pos| apples.list.contains_key(&pos));
 
-            if apples.list.contains_key(&head) {
-                apple_ev.send(AppleEv::Despawn(head));
-                apple_ev.send(AppleEv::SpawnRandom);
-            } else {
-                let len = snake.body.len();
-                snake.tail_dir = snake.body[len - 2] - snake.body[len - 1];
+                if walls.list.contains_key(&head) {
+                    damage_ev.send(DamageSnakeEv {
+                        snake_id: snake.id,
+                        snake_pos: 0,
+                    });
+                }
 
-                // Shrink Snake
-                snake.body.remove(len - 1);
+                if apples.list.contains_key(&head) {
+                    apple_ev.send(AppleEv::Despawn(head));
+                }
             }
         }
 
@@ -147,39 +297,60 @@ pub fn snake_system(
             head - neck
         };
 
-        let interpolation = if settings.interpolation {
+        let mut interpolation = if settings.interpolation {
             timer.0.elapsed_secs() / timer.0.duration().as_secs_f32() - 0.5
         } else {
             0.0
         };
-        let mesh = mesh_snake(&snake, interpolation);
-        *mesh_handle = meshes.add(mesh).into();
+        if settings.interpolation_easing {
+            interpolation = ease_in_out(interpolation);
+        }
+        let head_interpolation = if settings.head_interpolation {
+            interpolation
+        } else {
+            0.0
+        };
+        let tail_interpolation = if settings.tail_interpolation {
+            interpolation
+        } else {
+            0.0
+        };
+        let mesh = mesh_snake(
+            &snake,
+            head_interpolation,
+            tail_interpolation,
+            settings.snake_style,
+        );
+        update_mesh(&mut meshes, &mut mesh_handle, mesh);
     }
 
     // Handle end game
     if timer.0.just_finished() {
         'outer: for (snake, _) in snake_query.iter() {
-            let new_head = snake.body[0];
-            if !in_bounds(new_head, &b) {
-                damage_ev.send(DamageSnakeEv {
-                    snake_id: snake.id,
-                    snake_pos: 0,
-                });
-                continue 'outer;
-            }
+            // check every cell the head passed through this tick
+            for step in 0..snake.steps.min(snake.body.len()) {
+                let new_head = snake.body[step];
+                if !in_bounds(new_head, &b) {
+                    damage_ev.send(DamageSnakeEv {
+                        snake_id: snake.id,
+                        snake_pos: 0,
+                    });
+                    continue 'outer;
+                }
 
-            for (other_snake, _) in snake_query.iter() {
-                for i in 0..other_snake.body.len() {
-                    if snake.id == other_snake.id && i == 0 {
-                        continue;
-                    }
+                for (other_snake, _) in snake_query.iter() {
+                    for i in 0..other_snake.body.len() {
+                        if snake.id == other_snake.id && i == step {
+                            continue;
+                        }
 
-                    if other_snake.body[i] == new_head {
-                        damage_ev.send(DamageSnakeEv {
-                            snake_id: snake.id,
-                            snake_pos: 0,
-                        });
-                        continue 'outer;
+                        if other_snake.body[i] == new_head {
+                            damage_ev.send(DamageSnakeEv {
+                                snake_id: snake.id,
+                                snake_pos: 0,
+                            });
+                            continue 'outer;
+                        }
                     }
                 }
             }
@@ -187,9 +358,100 @@ pub fn snake_system(
     }
 }
 
+/// How presses between two ticks turn into moves
+#[derive(PartialEq, Eq, Clone, Copy)]
+pub enum InputMode {
+    /// Every press is buffered and played out one per tick
+    Queue,
+    /// Only the last press before the tick counts
+    LatestWins,
+}
+
+/// Directions waiting to be taken, one per tick
+#[derive(Default, Deref)]
+pub struct InputQueue(VecDeque<Direction>);
+
+impl InputQueue {
+    /// Queues `dir` unless it is a no-op or undoes the last queued turn.
+    /// `forward` is the direction the snake is currently moving. With
+    /// `preempt` a press that would be rejected replaces the whole queue
+    /// instead, as long as it is a valid turn from `forward`.
+    pub fn push(
+        &mut self,
+        dir: Direction,
+        forward: Direction,
+        depth: usize,
+        preempt: bool,
+    ) -> Result<(), InputRejection> {
+        let last = *self.0.back().unwrap_or(&forward);
+        let rejection = if self.0.len() >= depth {
+            InputRejection::QueueFull
+        } else if dir == last {
+            InputRejection::SameDirection
+        } else if dir == last.opposite() {
+            // this also catches quick wiggles like up, left, right
+            InputRejection::Reverse
+        } else {
+            self.0.push_back(dir);
+            return Ok(());
+        };
+
+        if preempt && dir != forward && dir != forward.opposite() {
+            self.0.clear();
+            self.0.push_back(dir);
+            return Ok(());
+        }
+
+        Err(rejection)
+    }
+
+    /// Swaps whatever is queued for `dir`, so only the last press before a
+    /// tick counts. Pressing `forward` just cancels the pending turn.
+    pub fn replace(&mut self, dir: Direction, forward: Direction) -> Result<(), InputRejection> {
+        if dir == forward.opposite() {
+            return Err(InputRejection::Reverse);
+        }
+
+        self.0.clear();
+        if dir != forward {
+            self.0.push_back(dir);
+        }
+        Ok(())
+    }
+
+    pub fn pop_front(&mut self) -> Option<Direction> {
+        self.0.pop_front()
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum InputRejection {
+    Reverse,
+    SameDirection,
+    QueueFull,
+}
+
+/// Sent when a direction press is dropped instead of queued
+#[derive(Event)]
+pub struct InputRejectedEv {
+    pub snake_id: u32,
+    pub reason: InputRejection,
+}
+
+/// Overwrites the snake's mesh in place so a new asset isn't made every frame
+fn update_mesh(meshes: &mut Assets<Mesh>, mesh_handle: &mut Mesh2dHandle, mesh: Mesh) {
+    if let Some(existing) = meshes.get_mut(&mesh_handle.0) {
+        *existing = mesh;
+    } else {
+        *mesh_handle = meshes.add(mesh).into();
+    }
+}
+
 #[derive(Resource)]
 pub struct Points {
-    pub points: [u32; 4],
+    pub points: Vec<u32>,
+    /// `points` as they were when the current round started
+    pub round_start: Vec<u32>,
 }
 
 #[derive(Event)]
@@ -198,14 +460,16 @@ pub struct DamageSnakeEv {
     pub snake_pos: usize,
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn damage_snake_system(
     mut commands: Commands,
     mut damage_snake_ev: EventReader<DamageSnakeEv>,
     mut snake_query: Query<(&mut Snake, Entity)>,
     mut points: ResMut<Points>,
+    mut respawns: ResMut<Respawns>,
     mut apple_ev: EventWriter<AppleEv>,
-    mut next_game_state: ResMut<NextState<GameState>>,
     settings: Res<Settings>,
+    b: Res<Board>,
 ) {
     let mut dead_snakes = Vec::new();
 
@@ -213,8 +477,8 @@ pub fn damage_snake_system(
         for (mut snake, snake_entity) in snake_query.iter_mut() {
             if snake.id == ev.snake_id {
                 if ev.snake_pos < 2 {
-                    if settings.snake_count == 1 {
-                        next_game_state.set(GameState::GameOver);
+                    if b.players == 1 {
+                        snake.dead = true;
                         return;
                     } else {
                         if snake.body.len() > 0 {
@@ -223,6 +487,12 @@ pub fn damage_snake_system(
 
                         commands.entity(snake_entity).despawn();
                         dead_snakes.push(snake.id);
+
+                        if let Some(delay) = settings.respawn_delay {
+                            if !respawns.list.iter().any(|(id, _)| *id == snake.id) {
+                                respawns.list.push((snake.id, delay));
+                            }
+                        }
                     }
                 }
 
@@ -244,7 +514,71 @@ pub fn damage_snake_system(
     }
 }
 
-#[derive(PartialEq, Clone, Copy)]
+#[derive(Resource)]
+pub struct Respawns {
+    /// Dead snakes waiting to respawn, as (snake id, ticks remaining)
+    pub list: Vec<(u32, u32)>,
+}
+
+#[allow(clippy::too_many_arguments)]
+fn respawn_system(
+    mut commands: Commands,
+    mut respawns: ResMut<Respawns>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    snake_query: Query<&Snake>,
+    timer: Res<MovmentTimer>,
+    apples: Res<Apples>,
+    walls: Res<Walls>,
+    colours: Res<Colours>,
+    gamepads: Res<Gamepads>,
+    settings: Res<Settings>,
+    b: Res<Board>,
+) {
+    if !timer.0.just_finished() {
+        return;
+    }
+
+    let positions = spawn_positions(&b, border_thickness(&settings, &b));
+    let snakes: Vec<&Snake> = snake_query.iter().collect();
+    let is_free = |pos: &IVec2| apples::is_legal_spawn(*pos, &b, &walls, &apples, &snakes);
+
+    let transform = Transform::from_xyz(-b.width as f32 / 2.0, -b.height as f32 / 2.0, 0.0);
+
+    respawns.list.retain_mut(|(id, ticks)| {
+        if *ticks > 0 {
+            *ticks -= 1;
+            return true;
+        }
+
+        // try the snakes own spawn first, then any other free one
+        let id = *id as usize;
+        let body = (0..positions.len())
+            .map(|i| &positions[(id + i) % positions.len()])
+            .find(|body| body.iter().all(is_free));
+
+        if let Some(body) = body {
+            commands.spawn((
+                MaterialMesh2dBundle {
+                    material: materials.add(ColorMaterial::from(colours.colours[id])),
+                    transform,
+                    ..default()
+                },
+                Snake {
+                    id: id as u32,
+                    body: body.clone(),
+                    controls: controls(id, &gamepads),
+                    ..Default::default()
+                },
+            ));
+            return false;
+        }
+
+        // no free spawn, try again next tick
+        true
+    });
+}
+
+#[derive(PartialEq, Clone, Copy, Debug)]
 pub enum Direction {
     Up,
     Down,
@@ -252,6 +586,17 @@ pub enum Direction {
     Right,
 }
 
+impl Direction {
+    pub fn opposite(self) -> Direction {
+        match self {
+            Direction::Up => Direction::Down,
+            Direction::Down => Direction::Up,
+            Direction::Left => Direction::Right,
+            Direction::Right => Direction::Left,
+        }
+    }
+}
+
 pub const DIR: [[i32; 2]; 4] = [[0, 1], [0, -1], [-1, 0], [1, 0]];
 
 pub fn get_direction(dir: IVec2) -> Direction {
@@ -263,3 +608,240 @@ pub fn get_direction(dir: IVec2) -> Direction {
         _ => panic!("Invalid direction"),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn queued(queue: &InputQueue) -> Vec<Direction> {
+        queue.iter().copied().collect()
+    }
+
+    #[test]
+    fn push_rejects_when_full() {
+        let mut queue = InputQueue::default();
+        for dir in [Direction::Up, Direction::Left, Direction::Down] {
+            assert_eq!(queue.push(dir, Direction::Right, 3, false), Ok(()));
+        }
+        assert_eq!(
+            queue.push(Direction::Right, Direction::Right, 3, false),
+            Err(InputRejection::QueueFull)
+        );
+        assert_eq!(
+            queued(&queue),
+            vec![Direction::Up, Direction::Left, Direction::Down]
+        );
+    }
+
+    #[test]
+    fn push_rejects_same_direction() {
+        let mut queue = InputQueue::default();
+        assert_eq!(
+            queue.push(Direction::Right, Direction::Right, 3, false),
+            Err(InputRejection::SameDirection)
+        );
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn push_rejects_reverse() {
+        let mut queue = InputQueue::default();
+        assert_eq!(
+            queue.push(Direction::Left, Direction::Right, 3, false),
+            Err(InputRejection::Reverse)
+        );
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn push_rejects_wiggle_back_into_body() {
+        // moving up, a quick left then right would turn back on the neck
+        let mut queue = InputQueue::default();
+        assert_eq!(queue.push(Direction::Left, Direction::Up, 3, false), Ok(()));
+        assert_eq!(
+            queue.push(Direction::Right, Direction::Up, 3, false),
+            Err(InputRejection::Reverse)
+        );
+        assert_eq!(queued(&queue), vec![Direction::Left]);
+    }
+
+    #[test]
+    fn push_preempt_replaces_queue() {
+        let mut queue = InputQueue::default();
+        assert_eq!(queue.push(Direction::Left, Direction::Up, 3, true), Ok(()));
+        assert_eq!(queue.push(Direction::Right, Direction::Up, 3, true), Ok(()));
+        assert_eq!(queued(&queue), vec![Direction::Right]);
+    }
+
+    #[test]
+    fn queue_mode_keeps_both_presses_in_a_tick() {
+        let mut queue = InputQueue::default();
+        assert_eq!(
+            queue.push(Direction::Up, Direction::Right, 3, false),
+            Ok(())
+        );
+        assert_eq!(
+            queue.push(Direction::Left, Direction::Right, 3, false),
+            Ok(())
+        );
+        assert_eq!(queued(&queue), vec![Direction::Up, Direction::Left]);
+    }
+
+    #[test]
+    fn latest_wins_keeps_the_later_press() {
+        let mut queue = InputQueue::default();
+        assert_eq!(queue.replace(Direction::Up, Direction::Right), Ok(()));
+        assert_eq!(queue.replace(Direction::Down, Direction::Right), Ok(()));
+        assert_eq!(queued(&queue), vec![Direction::Down]);
+    }
+
+    #[test]
+    fn latest_wins_rejects_reverse() {
+        let mut queue = InputQueue::default();
+        assert_eq!(queue.replace(Direction::Up, Direction::Right), Ok(()));
+        assert_eq!(
+            queue.replace(Direction::Left, Direction::Right),
+            Err(InputRejection::Reverse)
+        );
+        assert_eq!(queued(&queue), vec![Direction::Up]);
+    }
+
+    #[test]
+    fn latest_wins_forward_cancels_the_turn() {
+        let mut queue = InputQueue::default();
+        assert_eq!(queue.replace(Direction::Up, Direction::Right), Ok(()));
+        assert_eq!(queue.replace(Direction::Right, Direction::Right), Ok(()));
+        assert!(queue.is_empty());
+    }
+
+    fn moving_right() -> Snake {
+        Snake {
+            body: vec![IVec2::new(2, 1), IVec2::new(1, 1), IVec2::new(0, 1)],
+            ..Default::default()
+        }
+    }
+
+    fn double_turn_tick(snake: &mut Snake) {
+        for _ in 0..snake.steps_this_tick(true) {
+            snake.step(|_| false);
+        }
+    }
+
+    #[test]
+    fn double_turn_takes_both_turns() {
+        let mut snake = moving_right();
+        for dir in [Direction::Up, Direction::Left] {
+            assert_eq!(
+                snake.input_queue.push(dir, Direction::Right, 3, false),
+                Ok(())
+            );
+        }
+
+        double_turn_tick(&mut snake);
+
+        assert_eq!(
+            snake.body,
+            vec![IVec2::new(1, 2), IVec2::new(2, 2), IVec2::new(2, 1)]
+        );
+        assert!(!snake.body[1..].contains(&snake.body[0]));
+    }
+
+    #[test]
+    fn double_turn_cannot_reverse_into_body() {
+        // up then down would land the head back on the neck
+        let mut snake = moving_right();
+        assert_eq!(
+            snake
+                .input_queue
+                .push(Direction::Up, Direction::Right, 3, false),
+            Ok(())
+        );
+        assert_eq!(
+            snake
+                .input_queue
+                .push(Direction::Down, Direction::Right, 3, false),
+            Err(InputRejection::Reverse)
+        );
+        assert_eq!(
+            snake
+                .input_queue
+                .push(Direction::Left, Direction::Right, 3, false),
+            Ok(())
+        );
+
+        double_turn_tick(&mut snake);
+
+        assert_eq!(snake.body[0], IVec2::new(1, 2));
+        assert!(!snake.body[1..].contains(&snake.body[0]));
+    }
+
+    #[test]
+    fn single_turn_waits_for_the_next_tick() {
+        let mut snake = moving_right();
+        assert_eq!(
+            snake
+                .input_queue
+                .push(Direction::Up, Direction::Right, 3, false),
+            Ok(())
+        );
+
+        double_turn_tick(&mut snake);
+
+        assert_eq!(snake.body[0], IVec2::new(2, 2));
+        assert!(snake.input_queue.is_empty());
+    }
+
+    #[test]
+    fn head_can_chase_its_tail_forever() {
+        // coiled up in a 2x2 square, every move goes into the cell the tail
+        // is leaving
+        let mut snake = Snake {
+            body: vec![
+                IVec2::new(0, 0),
+                IVec2::new(0, 1),
+                IVec2::new(1, 1),
+                IVec2::new(1, 0),
+            ],
+            ..Default::default()
+        };
+        let turns = [
+            Direction::Right,
+            Direction::Up,
+            Direction::Left,
+            Direction::Down,
+        ];
+
+        for tick in 0..100 {
+            let forward = get_direction(snake.body[0] - snake.body[1]);
+            let turn = turns[tick % turns.len()];
+            assert_eq!(snake.input_queue.push(turn, forward, 3, false), Ok(()));
+
+            let tail = *snake.body.last().unwrap();
+            let head = snake.step(|_| false);
+
+            assert_eq!(head, tail);
+            assert_eq!(snake.body.len(), 4);
+            assert!(!snake.body[1..].contains(&head), "tick {tick}");
+        }
+    }
+
+    #[test]
+    fn held_stick_queues_one_turn() {
+        let mut snake = moving_right();
+        let hold = |snake: &mut Snake, stick: Option<Direction>, frames: usize| {
+            for _ in 0..frames {
+                if let Some(dir) = snake.stick_press(stick) {
+                    let _ = snake.input_queue.push(dir, Direction::Right, 3, false);
+                }
+            }
+        };
+
+        hold(&mut snake, Some(Direction::Up), 10);
+        assert_eq!(snake.input_queue.len(), 1);
+
+        // back to neutral, then pushed again
+        hold(&mut snake, None, 1);
+        hold(&mut snake, Some(Direction::Left), 10);
+        assert_eq!(snake.input_queue.len(), 2);
+    }
+}
@@ -0,0 +1,99 @@
+use super::*;
+
+pub struct SnapshotPlugin;
+
+impl Plugin for SnapshotPlugin {
+    #[cfg_attr(target_arch = "wasm32", allow(unused_variables))]
+    fn build(&self, app: &mut App) {
+        // writing files isn't supported on the web
+        #[cfg(not(target_arch = "wasm32"))]
+        app.add_systems(Update, snapshot_system);
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn snapshot_system(
+    keys: Res<ButtonInput<KeyCode>>,
+    snake_query: Query<&Snake>,
+    bullet_query: Query<&Bullet>,
+    apples: Res<Apples>,
+    walls: Res<Walls>,
+    colours: Res<Colours>,
+    b: Res<Board>,
+) {
+    use bevy::render::{
+        render_asset::RenderAssetUsages,
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+    };
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    if !keys.just_pressed(KeyCode::F2) {
+        return;
+    }
+
+    // pixels per board cell
+    let scale = 16;
+
+    let mut cells = HashMap::new();
+    for pos in walls.list.keys() {
+        cells.insert(*pos, Color::srgb(0.1, 0.1, 0.1));
+    }
+    for pos in walls.moving.iter() {
+        cells.insert(*pos, Color::srgb(0.3, 0.08, 0.08));
+    }
+    for pos in apples.list.keys() {
+        cells.insert(*pos, apple_colour(colours.palette));
+    }
+    for snake in snake_query.iter() {
+        for pos in snake.body.iter() {
+            cells.insert(*pos, colours.colours[snake.id as usize]);
+        }
+    }
+    for bullet in bullet_query.iter() {
+        cells.insert(bullet.pos, Color::srgb(1.0, 1.0, 0.26));
+    }
+
+    let width = b.width as u32 * scale;
+    let height = b.height as u32 * scale;
+    let mut image = Image::new_fill(
+        Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        &[0, 0, 0, 255],
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::default(),
+    );
+
+    for px in 0..width {
+        for py in 0..height {
+            // images go top to bottom but the board goes bottom to top
+            let pos = IVec2::new((px / scale) as i32, b.height - 1 - (py / scale) as i32);
+            let color = cells
+                .get(&pos)
+                .copied()
+                .unwrap_or(if (pos.x + pos.y) % 2 == 0 {
+                    b.colour1
+                } else {
+                    b.colour2
+                });
+
+            let i = ((py * width + px) * 4) as usize;
+            image.data[i..i + 4].copy_from_slice(&color.to_srgba().to_u8_array());
+        }
+    }
+
+    let time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_secs())
+        .unwrap_or_default();
+    let path = format!("snake-{}.png", time);
+
+    match image.try_into_dynamic().map(|image| image.save(&path)) {
+        Ok(Ok(())) => info!("Saved board snapshot to {}", path),
+        Ok(Err(err)) => warn!("Failed to save board snapshot: {}", err),
+        Err(err) => warn!("Failed to convert board snapshot: {}", err),
+    }
+}
@@ -0,0 +1,71 @@
+use super::*;
+use bevy::audio::Volume;
+
+pub struct SoundPlugin;
+
+impl Plugin for SoundPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, setup)
+            .add_systems(Update, volume_system)
+            .add_systems(Update, sound_system.run_if(in_state(GameState::InGame)));
+    }
+}
+
+#[derive(Resource)]
+struct Sounds {
+    boom: Handle<AudioSource>,
+}
+
+fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(Sounds {
+        boom: asset_server.load("sounds/boom.ogg"),
+    });
+}
+
+fn volume_system(mut global_volume: ResMut<GlobalVolume>, settings: Res<Settings>) {
+    if global_volume.volume.get() != settings.volume {
+        global_volume.volume = Volume::new(settings.volume);
+    }
+}
+
+// browsers keep audio suspended until the first click or key press, anything
+// played before that is just dropped so this is safe to run on the web too
+fn sound_system(
+    mut commands: Commands,
+    mut apple_ev: EventReader<AppleEv>,
+    mut damage_ev: EventReader<DamageSnakeEv>,
+    mut bullet_spawn_ev: EventReader<SpawnBulletEv>,
+    mut explosion_ev: EventReader<ExplosionEv>,
+    sounds: Res<Sounds>,
+) {
+    // there's only the one sample so pitch it around for the smaller events
+    let mut play = |speed: f32, volume: f32| {
+        commands.spawn(AudioBundle {
+            source: sounds.boom.clone(),
+            settings: PlaybackSettings::DESPAWN
+                .with_speed(speed)
+                .with_volume(Volume::new(volume)),
+        });
+    };
+
+    for ev in apple_ev.read() {
+        // apples only get despawned when eaten
+        if let AppleEv::Despawn(_) = ev {
+            play(3.0, 0.3);
+        }
+    }
+    for ev in damage_ev.read() {
+        // hits to the head or neck kill the snake
+        if ev.snake_pos < 2 {
+            play(0.5, 1.0);
+        } else {
+            play(0.8, 0.6);
+        }
+    }
+    for _ in bullet_spawn_ev.read() {
+        play(2.0, 0.2);
+    }
+    for _ in explosion_ev.read() {
+        play(1.0, 1.0);
+    }
+}
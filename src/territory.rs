@@ -0,0 +1,99 @@
+use super::*;
+use snake::DIR;
+use std::collections::HashSet;
+
+pub struct TerritoryPlugin;
+
+impl Plugin for TerritoryPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            territory_system
+                .run_if(in_state(GameState::InGame))
+                .after(snake::snake_system),
+        );
+    }
+}
+
+#[derive(Component)]
+struct TerritoryGizmo;
+
+// colours each free cell by the snake whose head can reach it first, cells
+// two snakes reach at the same time stay uncoloured
+fn territory_system(
+    mut commands: Commands,
+    snake_query: Query<&Snake>,
+    gizmo_query: Query<Entity, With<TerritoryGizmo>>,
+    walls: Res<Walls>,
+    colours: Res<Colours>,
+    b: Res<Board>,
+    settings: Res<Settings>,
+) {
+    for entity in gizmo_query.iter() {
+        commands.entity(entity).despawn();
+    }
+
+    if !settings.territory_debug {
+        return;
+    }
+
+    let blocked: HashSet<IVec2> = snake_query
+        .iter()
+        .flat_map(|snake| snake.body.iter().copied())
+        .chain(walls.list.keys().copied())
+        .collect();
+
+    // (distance, owner) where `None` means contested
+    let mut owners: HashMap<IVec2, (u32, Option<u32>)> = HashMap::new();
+    let mut queue = VecDeque::new();
+    for snake in snake_query.iter() {
+        if let Some(head) = snake.body.first() {
+            queue.push_back((*head, 0, snake.id));
+        }
+    }
+
+    while let Some((pos, distance, id)) = queue.pop_front() {
+        // a contested cell doesn't spread further for anyone
+        if owners.get(&pos).is_some_and(|(_, owner)| owner.is_none()) {
+            continue;
+        }
+
+        for dir in DIR {
+            let next = pos + IVec2::from(dir);
+            if !in_bounds(next, &b) || blocked.contains(&next) {
+                continue;
+            }
+
+            match owners.get_mut(&next) {
+                None => {
+                    owners.insert(next, (distance + 1, Some(id)));
+                    queue.push_back((next, distance + 1, id));
+                }
+                Some((d, owner)) if *d == distance + 1 && *owner != Some(id) => *owner = None,
+                Some(_) => {}
+            }
+        }
+    }
+
+    for (pos, (_, owner)) in owners {
+        let Some(id) = owner else {
+            continue;
+        };
+
+        commands.spawn((
+            SpriteBundle {
+                sprite: Sprite {
+                    color: colours.colours[id as usize].with_alpha(0.25),
+                    ..default()
+                },
+                transform: Transform::from_xyz(
+                    pos.x as f32 - b.width as f32 / 2.0 + 0.5,
+                    pos.y as f32 - b.height as f32 / 2.0 + 0.5,
+                    4.0,
+                ),
+                ..default()
+            },
+            TerritoryGizmo,
+        ));
+    }
+}
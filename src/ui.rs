@@ -1,4 +1,5 @@
 use super::*;
+use bevy::diagnostic::{DiagnosticsStore, FrameTimeDiagnosticsPlugin};
 use bevy_inspector_egui::{
     bevy_egui::{EguiContexts, EguiPlugin},
     egui,
@@ -8,15 +9,18 @@ pub struct UiPlugin;
 
 impl Plugin for UiPlugin {
     fn build(&self, app: &mut App) {
-        app.add_plugins(EguiPlugin)
+        app.add_plugins((EguiPlugin, FrameTimeDiagnosticsPlugin))
             .add_systems(Startup, ui_setup)
-            .add_systems(Update, ui_system);
+            .add_systems(Update, (ui_system, input_feedback_system));
     }
 }
 
 #[derive(Component)]
 struct PointId(u32);
 
+#[derive(Component)]
+struct InputFeedback(Timer);
+
 fn ui_setup(mut commands: Commands, asset_server: Res<AssetServer>, colours: Res<Colours>) {
     // point counters
     commands
@@ -34,7 +38,7 @@ fn ui_setup(mut commands: Commands, asset_server: Res<AssetServer>, colours: Res
             ..default()
         })
         .with_children(|parent| {
-            for i in 0..4 {
+            for i in 0..MAX_PLAYERS {
                 parent
                     .spawn(TextBundle {
                         text: Text {
@@ -59,8 +63,69 @@ fn ui_setup(mut commands: Commands, asset_server: Res<AssetServer>, colours: Res
                     .insert(PointId(i as u32));
             }
         });
+
+    // input feedback
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                justify_content: JustifyContent::Center,
+                bottom: Val::Px(20.0),
+                width: Val::Percent(100.0),
+                ..default()
+            },
+            background_color: Color::NONE.into(),
+            ..default()
+        })
+        .with_children(|parent| {
+            parent.spawn((
+                TextBundle {
+                    text: Text::from_section(
+                        "",
+                        TextStyle {
+                            font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                            font_size: 30.0,
+                            color: Color::WHITE,
+                        },
+                    ),
+                    ..default()
+                },
+                InputFeedback(Timer::from_seconds(1.0, TimerMode::Once)),
+            ));
+        });
 }
 
+fn input_feedback_system(
+    mut feedback_query: Query<(&mut InputFeedback, &mut Text)>,
+    mut input_rejected_ev: EventReader<snake::InputRejectedEv>,
+    colours: Res<Colours>,
+    settings: Res<Settings>,
+    time: Res<Time>,
+) {
+    let (mut feedback, mut text) = feedback_query.single_mut();
+
+    for ev in input_rejected_ev.read() {
+        if !settings.show_input_feedback {
+            continue;
+        }
+
+        text.sections[0].value = match ev.reason {
+            snake::InputRejection::Reverse => "Can't reverse",
+            snake::InputRejection::SameDirection => "Already going that way",
+            snake::InputRejection::QueueFull => "Input queue full",
+        }
+        .to_string();
+        text.sections[0].style.color = colours.colours[ev.snake_id as usize];
+        feedback.0.reset();
+    }
+
+    feedback.0.tick(time.delta());
+    if feedback.0.just_finished() || !settings.show_input_feedback {
+        text.sections[0].value.clear();
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 fn ui_system(
     mut point_query: Query<(&PointId, &mut Text, &mut Style)>,
     points: Res<snake::Points>,
@@ -68,14 +133,39 @@ fn ui_system(
     mut settings: ResMut<Settings>,
     mut wall_ev: EventWriter<WallEv>,
     snake_query: Query<&Snake>,
+    b: Res<Board>,
+    game_rng: Res<GameRng>,
+    winner: Res<Winner>,
+    colours: Res<Colours>,
+    diagnostics: Res<DiagnosticsStore>,
 ) {
     egui::Window::new("Settings").show(contexts.ctx_mut(), |ui| {
+        if let Some(id) = winner.0 {
+            ui.label(format!("Snake {} wins!", id + 1));
+        }
         ui.label(format!("tps: {:.1}", settings.tps));
+        if let Some(frame_time) = diagnostics
+            .get(&FrameTimeDiagnosticsPlugin::FRAME_TIME)
+            .and_then(|frame_time| frame_time.smoothed())
+        {
+            ui.label(format!("frame time: {:.2}ms", frame_time));
+        }
+
+        let mut paused = !settings.do_game_tick;
+        ui.checkbox(&mut paused, "Pause");
+        settings.do_game_tick = !paused;
 
-        ui.add(egui::Slider::new(&mut settings.snake_count, 1..=4).text("Players"));
+        ui.add(egui::Slider::new(&mut settings.volume, 0.0..=1.0).text("Volume"));
+
+        ui.add(
+            egui::Slider::new(&mut settings.snake_count, 1..=MAX_PLAYERS as u32).text("Players"),
+        );
 
         ui.checkbox(&mut settings.tps_ramp, "Speed ramp");
         if !settings.tps_ramp {
+            ui.checkbox(&mut settings.length_speed, "Speed up with length");
+        }
+        if !settings.tps_ramp && !settings.length_speed {
             ui.horizontal(|ui| {
                 ui.label("Speed: ");
                 ui.selectable_value(&mut settings.tps, 5.0, "Slow");
@@ -84,6 +174,52 @@ fn ui_system(
             });
         }
 
+        ui.checkbox(&mut settings.double_turn, "Double turn");
+        ui.checkbox(&mut settings.show_input_feedback, "Show input feedback");
+        ui.horizontal(|ui| {
+            ui.label("Input: ");
+            ui.selectable_value(&mut settings.input_mode, InputMode::Queue, "Queue");
+            ui.selectable_value(
+                &mut settings.input_mode,
+                InputMode::LatestWins,
+                "Latest wins",
+            );
+        });
+        if settings.input_mode == InputMode::Queue {
+            ui.horizontal(|ui| {
+                ui.add(
+                    egui::Slider::new(&mut settings.input_buffer_depth, 1..=5).text("Input buffer"),
+                );
+                ui.checkbox(&mut settings.input_preempt, "Preempt");
+            });
+        }
+
+        ui.checkbox(&mut settings.interpolation, "Interpolation");
+        if settings.interpolation {
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut settings.interpolation_easing, "Easing");
+                ui.checkbox(&mut settings.head_interpolation, "Head");
+                ui.checkbox(&mut settings.tail_interpolation, "Tail");
+            });
+        }
+
+        ui.horizontal(|ui| {
+            ui.label("Snake style: ");
+            ui.selectable_value(&mut settings.snake_style, SnakeStyle::Circles, "Circles");
+            ui.selectable_value(&mut settings.snake_style, SnakeStyle::Capsule, "Capsule");
+            ui.selectable_value(&mut settings.snake_style, SnakeStyle::Blocky, "Blocky");
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("Palette: ");
+            ui.selectable_value(&mut settings.palette, Palette::Default, "Default");
+            ui.selectable_value(&mut settings.palette, Palette::Deuteranopia, "Deuteranopia");
+            ui.selectable_value(&mut settings.palette, Palette::Protanopia, "Protanopia");
+            ui.selectable_value(&mut settings.palette, Palette::Tritanopia, "Tritanopia");
+        });
+
+        ui.add(egui::Slider::new(&mut settings.camera_margin, 0.0..=3.0).text("Camera margin"));
+
         ui.horizontal(|ui| {
             ui.label("Board size: ");
             ui.selectable_value(&mut settings.board_size, BoardSize::Small, "Small");
@@ -91,31 +227,156 @@ fn ui_system(
             ui.selectable_value(&mut settings.board_size, BoardSize::Large, "Large");
         });
 
+        let mut scale_apples = settings.apples_per_snake.is_some();
+        ui.checkbox(&mut scale_apples, "Scale apples with players");
+        if scale_apples {
+            let mut apples_per_snake = settings.apples_per_snake.unwrap_or(1.0);
+            ui.add(egui::Slider::new(&mut apples_per_snake, 0.5..=2.0).text("Apples per player"));
+            settings.apples_per_snake = Some(apples_per_snake);
+        } else {
+            settings.apples_per_snake = None;
+
+            ui.horizontal(|ui| {
+                ui.label("Apples: ");
+                ui.selectable_value(&mut settings.apple_count, 1, "One");
+                ui.selectable_value(&mut settings.apple_count, 3, "Three");
+                ui.selectable_value(&mut settings.apple_count, 5, "Five");
+            });
+        }
+
+        ui.horizontal(|ui| {
+            ui.label("Apple placement: ");
+            ui.selectable_value(
+                &mut settings.apple_strategy,
+                AppleStrategy::Random,
+                "Random",
+            );
+            ui.selectable_value(
+                &mut settings.apple_strategy,
+                AppleStrategy::Farthest,
+                "Farthest",
+            );
+            ui.selectable_value(
+                &mut settings.apple_strategy,
+                AppleStrategy::Weighted,
+                "Weighted",
+            );
+            if ui
+                .selectable_label(
+                    matches!(settings.apple_strategy, AppleStrategy::Sequence(_)),
+                    "Sequence",
+                )
+                .clicked()
+            {
+                // sweep the board row by row for fully repeatable games
+                let sweep = (0..b.height)
+                    .flat_map(|y| (0..b.width).map(move |x| IVec2::new(x, y)))
+                    .collect();
+                settings.apple_strategy = AppleStrategy::Sequence(sweep);
+            }
+        });
+
+        let mut respawn = settings.respawn_delay.is_some();
+        ui.checkbox(&mut respawn, "Respawn");
+        if respawn {
+            let mut delay = settings.respawn_delay.unwrap_or(10);
+            ui.add(egui::Slider::new(&mut delay, 0..=50).text("Respawn delay (ticks)"));
+            settings.respawn_delay = Some(delay);
+        } else {
+            settings.respawn_delay = None;
+        }
+
         ui.horizontal(|ui| {
-            ui.label("Apples: ");
-            ui.selectable_value(&mut settings.apple_count, 1, "One");
-            ui.selectable_value(&mut settings.apple_count, 3, "Three");
-            ui.selectable_value(&mut settings.apple_count, 5, "Five");
+            ui.label("Win by: ");
+            ui.selectable_value(
+                &mut settings.win_condition,
+                WinCondition::LastStanding,
+                "Last standing",
+            );
+            if ui
+                .selectable_label(
+                    matches!(settings.win_condition, WinCondition::FirstToScore(_)),
+                    "Score",
+                )
+                .clicked()
+            {
+                settings.win_condition = WinCondition::FirstToScore(5);
+            }
+            ui.selectable_value(
+                &mut settings.win_condition,
+                WinCondition::HighestAtTimeout,
+                "Timeout",
+            );
+            if ui
+                .selectable_label(
+                    matches!(settings.win_condition, WinCondition::LongestSnake(_)),
+                    "Length",
+                )
+                .clicked()
+            {
+                settings.win_condition = WinCondition::LongestSnake(20);
+            }
         });
+        match &mut settings.win_condition {
+            WinCondition::FirstToScore(score) => {
+                ui.add(egui::Slider::new(score, 1..=20).text("Points to win"));
+            }
+            WinCondition::LongestSnake(length) => {
+                ui.add(egui::Slider::new(length, 5..=100).text("Length to win"));
+            }
+            WinCondition::LastStanding | WinCondition::HighestAtTimeout => {}
+        }
+
+        let mut time_limit = settings.time_limit.is_some();
+        ui.checkbox(&mut time_limit, "Time limit");
+        if time_limit {
+            let mut limit = settings.time_limit.unwrap_or(RESPAWN_ROUND_TIME);
+            ui.add(egui::Slider::new(&mut limit, 10.0..=600.0).text("Round length (s)"));
+            settings.time_limit = Some(limit);
+        } else {
+            settings.time_limit = None;
+        }
+
+        ui.add(egui::Slider::new(&mut settings.explosion_radius, 0..=2).text("Explosion radius"));
 
         ui.checkbox(&mut settings.walls, "Walls");
+        if settings.walls {
+            ui.horizontal(|ui| {
+                ui.add(egui::Slider::new(&mut settings.border.thickness, 0..=3).text("Border"));
+                ui.checkbox(&mut settings.border.gates, "Gates");
+            });
+            ui.add(egui::Slider::new(&mut settings.moving_walls, 0..=5).text("Moving walls"));
+        }
         ui.checkbox(&mut settings.walls_debug, "Walls debug");
+        ui.checkbox(&mut settings.territory_debug, "Territory debug");
+        ui.checkbox(&mut settings.debug_restart, "Restart with R");
 
         if ui.button("Spawn wall").clicked() {
             wall_ev.send(WallEv::Spawn);
         }
 
+        ui.horizontal(|ui| {
+            ui.label(format!("Seed: {}", game_rng.seed));
+            let mut replay = settings.seed.is_some();
+            ui.checkbox(&mut replay, "Replay");
+            settings.seed = replay.then_some(game_rng.seed);
+        });
+
         ui.label("Controls");
         ui.label("Snake 1: WASD to move, LShift to shoot");
         ui.label("Snake 2: Arrows to move, RAlt to shoot");
         ui.label("Snake 3: PL;' to move, \\ to shoot");
         ui.label("Snake 4: YGHJ to move, B to shoot");
+        ui.label("Snake 5+: Gamepad d-pad or left stick to move, A to shoot");
         ui.label("Space to restart");
+        ui.label("F2 to save a board snapshot");
+        ui.label("Period to step while paused");
     });
 
     for (point_id, mut text, mut style) in point_query.iter_mut() {
         let id = point_id.0;
-        if settings.snake_count == 1 {
+        text.sections[0].style.color = colours.colours[id as usize];
+        if b.players == 1 {
             if id == 0 {
                 style.display = Display::Flex;
                 for snake in snake_query.iter() {
@@ -127,13 +388,14 @@ fn ui_system(
                 style.display = Display::None;
             }
         } else {
-            if points.points[id as usize] == 0 {
+            let points = points.points.get(id as usize).copied().unwrap_or(0);
+            if points == 0 {
                 style.display = Display::None;
             } else {
                 style.display = Display::Flex;
             }
 
-            text.sections[0].value = points.points[id as usize].to_string();
+            text.sections[0].value = points.to_string();
         }
     }
 }
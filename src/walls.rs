@@ -1,4 +1,7 @@
 use super::*;
+use rand::{seq::SliceRandom, Rng};
+use snake::DIR;
+use std::collections::HashSet;
 
 pub struct WallPlugin;
 
@@ -6,18 +9,31 @@ impl Plugin for WallPlugin {
     fn build(&self, app: &mut App) {
         app.add_systems(
             Update,
-            wall_system
-                .run_if(in_state(GameState::InGame))
-                .after(snake::damage_snake_system)
-                .after(snake::snake_system)
-                .after(reset_game),
+            (
+                wall_system
+                    .run_if(in_state(GameState::InGame))
+                    .after(snake::damage_snake_system)
+                    .after(snake::snake_system)
+                    .after(reset_game),
+                wall_tile_system.after(wall_system),
+                moving_wall_system
+                    .run_if(in_state(GameState::InGame))
+                    .after(snake::snake_system)
+                    .before(snake::damage_snake_system),
+            ),
+        )
+        .add_systems(
+            OnEnter(GameState::Start),
+            spawn_moving_walls.after(reset_game),
         );
     }
 }
 
-#[derive(Resource)]
+#[derive(Resource, Default)]
 pub struct Walls {
     pub list: HashMap<IVec2, Entity>,
+    /// Cells currently covered by moving walls
+    pub moving: HashSet<IVec2>,
 }
 
 #[derive(Event)]
@@ -29,18 +45,56 @@ pub enum WallEv {
 #[derive(Component)]
 struct DebugGizmo;
 
+/// A wall that walks back and forth along a straight line, hurting any snake
+/// it runs into
+#[derive(Component)]
+struct MovingWall {
+    pos: IVec2,
+    dir: IVec2,
+    /// Steps taken since the last turn around
+    travelled: i32,
+    path_len: i32,
+}
+
+impl MovingWall {
+    /// Steps once along the path, turning around at the end of it or when the
+    /// way is `blocked`. A wall boxed in on both sides stays put.
+    fn step(&mut self, blocked: impl Fn(IVec2) -> bool) {
+        if self.travelled >= self.path_len || blocked(self.pos + self.dir) {
+            self.dir = -self.dir;
+            self.travelled = self.path_len - self.travelled;
+        }
+
+        let next = self.pos + self.dir;
+        if !blocked(next) {
+            self.pos = next;
+            self.travelled += 1;
+        }
+    }
+}
+
+/// Whether a wall that moved from `last` to `pos` touches a snake, including
+/// a head that swapped places with it
+fn wall_hits_snake(last: IVec2, pos: IVec2, body: &[IVec2]) -> bool {
+    let swapped = pos != last && body.len() > 1 && body[0] == last && body[1] == pos;
+    swapped || body.contains(&pos)
+}
+
+/// Which neighbouring cells are also walls, one bit per entry in `DIR`
+#[derive(Component)]
+struct WallTile(u8);
+
 fn wall_system(
     mut walls: ResMut<Walls>,
     apples: Res<Apples>,
     snake_query: Query<&Snake>,
     mut commands: Commands,
     b: Res<Board>,
+    mut game_rng: ResMut<GameRng>,
     mut wall_ev: EventReader<WallEv>,
     settings: Res<Settings>,
     debug_gizmo_query: Query<Entity, With<DebugGizmo>>,
 ) {
-    let mut rng = rand::thread_rng();
-
     let unspawnable_positions = vec![
         IVec2::new(0, 1),
         IVec2::new(1, 0),
@@ -67,7 +121,10 @@ fn wall_system(
         }
 
         // stop walls spawning on other walls or on an apple
-        if walls.list.contains_key(&pos) || apples.list.contains_key(&pos) {
+        if walls.list.contains_key(&pos)
+            || walls.moving.contains(&pos)
+            || apples.list.contains_key(&pos)
+        {
             return false;
         }
 
@@ -116,38 +173,15 @@ fn wall_system(
     for wall_ev in wall_ev.read() {
         match wall_ev {
             WallEv::Spawn => {
-                let mut count = 0;
-                let mut pos;
-                'wall: loop {
-                    pos = IVec2::new(rng.gen_range(0..b.width), rng.gen_range(0..b.height));
-
-                    count += 1;
-                    if count > 1000 {
-                        return;
-                    }
-
-                    if is_valid(pos, &walls) {
-                        break 'wall;
-                    }
-                }
+                let valid: Vec<IVec2> = (0..b.width)
+                    .flat_map(|x| (0..b.height).map(move |y| IVec2::new(x, y)))
+                    .filter(|pos| is_valid(*pos, &walls))
+                    .collect();
+                let Some(&pos) = valid.choose(&mut game_rng.rng) else {
+                    return;
+                };
 
-                walls.list.insert(
-                    pos,
-                    commands
-                        .spawn(SpriteBundle {
-                            sprite: Sprite {
-                                color: Color::srgb(0.1, 0.1, 0.1),
-                                ..default()
-                            },
-                            transform: Transform::from_xyz(
-                                pos.x as f32 - b.width as f32 / 2.0 + 0.5,
-                                pos.y as f32 - b.height as f32 / 2.0 + 0.5,
-                                5.0,
-                            ),
-                            ..default()
-                        })
-                        .id(),
-                );
+                walls.list.insert(pos, spawn_wall(&mut commands, &b, pos));
             }
             WallEv::Destroy(pos) => {
                 if let Some(entity) = walls.list.remove(&pos) {
@@ -186,3 +220,244 @@ fn wall_system(
         }
     }
 }
+
+pub fn spawn_wall(commands: &mut Commands, b: &Board, pos: IVec2) -> Entity {
+    commands
+        .spawn((
+            SpriteBundle {
+                sprite: Sprite {
+                    color: Color::srgb(0.1, 0.1, 0.1),
+                    ..default()
+                },
+                transform: Transform::from_xyz(
+                    pos.x as f32 - b.width as f32 / 2.0 + 0.5,
+                    pos.y as f32 - b.height as f32 / 2.0 + 0.5,
+                    5.0,
+                ),
+                ..default()
+            },
+            WallTile(u8::MAX),
+        ))
+        .id()
+}
+
+/// Shrinks each wall away from the sides that don't touch another wall, so
+/// neighbouring walls join up into one shape.
+fn wall_tile_system(
+    walls: Res<Walls>,
+    mut tile_query: Query<(&mut WallTile, &mut Sprite, &mut Transform)>,
+    added_query: Query<(), Added<WallTile>>,
+    b: Res<Board>,
+) {
+    if !walls.is_changed() && added_query.is_empty() {
+        return;
+    }
+
+    let inset = 0.1;
+
+    for (pos, entity) in walls.list.iter() {
+        let Ok((mut tile, mut sprite, mut transform)) = tile_query.get_mut(*entity) else {
+            continue;
+        };
+
+        let mut mask = 0;
+        for (i, dir) in DIR.iter().enumerate() {
+            if walls.list.contains_key(&(*pos + IVec2::from(*dir))) {
+                mask |= 1 << i;
+            }
+        }
+
+        if tile.0 == mask {
+            continue;
+        }
+        tile.0 = mask;
+
+        let side = |bit: usize| {
+            if mask & (1 << bit) != 0 {
+                0.5
+            } else {
+                0.5 - inset
+            }
+        };
+        let (up, down, left, right) = (side(0), side(1), side(2), side(3));
+
+        sprite.custom_size = Some(Vec2::new(left + right, up + down));
+        transform.translation.x = pos.x as f32 - b.width as f32 / 2.0 + 0.5 + (right - left) / 2.0;
+        transform.translation.y = pos.y as f32 - b.height as f32 / 2.0 + 0.5 + (up - down) / 2.0;
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn spawn_moving_walls(
+    mut commands: Commands,
+    moving_wall_query: Query<Entity, With<MovingWall>>,
+    snake_query: Query<&Snake>,
+    mut walls: ResMut<Walls>,
+    apples: Res<Apples>,
+    mut game_rng: ResMut<GameRng>,
+    b: Res<Board>,
+    settings: Res<Settings>,
+) {
+    for entity in moving_wall_query.iter() {
+        commands.entity(entity).despawn();
+    }
+    walls.moving.clear();
+
+    if !settings.walls {
+        return;
+    }
+
+    // keep clear of the snakes so nobody gets hit straight away
+    let snakes: Vec<&Snake> = snake_query.iter().collect();
+    let mut valid: Vec<IVec2> = apples::spawnable(&b, &walls, &apples, &snakes)
+        .into_iter()
+        .filter(|pos| {
+            !snakes.iter().any(|snake| {
+                snake
+                    .body
+                    .first()
+                    .is_some_and(|head| (*head - *pos).abs().max_element() <= 3)
+            })
+        })
+        .collect();
+
+    for _ in 0..settings.moving_walls {
+        valid.shuffle(&mut game_rng.rng);
+        let Some(pos) = valid.pop() else {
+            return;
+        };
+        walls.moving.insert(pos);
+
+        commands.spawn((
+            SpriteBundle {
+                sprite: Sprite {
+                    color: Color::srgb(0.3, 0.08, 0.08),
+                    custom_size: Some(Vec2::splat(0.8)),
+                    ..default()
+                },
+                transform: Transform::from_xyz(
+                    pos.x as f32 - b.width as f32 / 2.0 + 0.5,
+                    pos.y as f32 - b.height as f32 / 2.0 + 0.5,
+                    5.0,
+                ),
+                ..default()
+            },
+            MovingWall {
+                pos,
+                dir: (*DIR.choose(&mut game_rng.rng).unwrap()).into(),
+                travelled: 0,
+                path_len: game_rng.rng.gen_range(2..=5),
+            },
+        ));
+    }
+}
+
+fn moving_wall_system(
+    mut moving_wall_query: Query<(&mut MovingWall, &mut Transform)>,
+    snake_query: Query<&Snake>,
+    mut walls: ResMut<Walls>,
+    timer: Res<MovmentTimer>,
+    b: Res<Board>,
+    mut damage_ev: EventWriter<DamageSnakeEv>,
+) {
+    if !timer.0.just_finished() {
+        return;
+    }
+
+    let mut moving = HashSet::new();
+    let mut hit_snakes = Vec::new();
+    for (mut wall, mut transform) in moving_wall_query.iter_mut() {
+        let last = wall.pos;
+        wall.step(|pos| !in_bounds(pos, &b) || walls.list.contains_key(&pos));
+        moving.insert(wall.pos);
+
+        transform.translation.x = wall.pos.x as f32 - b.width as f32 / 2.0 + 0.5;
+        transform.translation.y = wall.pos.y as f32 - b.height as f32 / 2.0 + 0.5;
+
+        // checked every tick, a stuck wall is still deadly
+        for snake in snake_query.iter() {
+            if wall_hits_snake(last, wall.pos, &snake.body) && !hit_snakes.contains(&snake.id) {
+                hit_snakes.push(snake.id);
+            }
+        }
+    }
+    walls.moving = moving;
+
+    // touching a moving wall anywhere kills the snake
+    for snake_id in hit_snakes {
+        damage_ev.send(DamageSnakeEv {
+            snake_id,
+            snake_pos: 0,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cells(cells: &[(i32, i32)]) -> Vec<IVec2> {
+        cells.iter().map(|&(x, y)| IVec2::new(x, y)).collect()
+    }
+
+    #[test]
+    fn wall_sweeps_into_a_stationary_snake() {
+        let body = cells(&[(3, 0), (3, 1), (3, 2)]);
+        let mut wall = MovingWall {
+            pos: IVec2::new(0, 1),
+            dir: IVec2::X,
+            travelled: 0,
+            path_len: 5,
+        };
+
+        let mut hits = Vec::new();
+        for _ in 0..3 {
+            let last = wall.pos;
+            wall.step(|_| false);
+            hits.push(wall_hits_snake(last, wall.pos, &body));
+        }
+
+        assert_eq!(wall.pos, IVec2::new(3, 1));
+        assert_eq!(hits, vec![false, false, true]);
+    }
+
+    #[test]
+    fn wall_turns_around_at_the_end_of_its_path() {
+        let mut wall = MovingWall {
+            pos: IVec2::ZERO,
+            dir: IVec2::X,
+            travelled: 0,
+            path_len: 2,
+        };
+
+        let mut path = Vec::new();
+        for _ in 0..4 {
+            wall.step(|_| false);
+            path.push(wall.pos);
+        }
+        assert_eq!(path, cells(&[(1, 0), (2, 0), (1, 0), (0, 0)]));
+    }
+
+    #[test]
+    fn boxed_in_wall_stays_put_and_still_hits() {
+        let mut wall = MovingWall {
+            pos: IVec2::new(1, 0),
+            dir: IVec2::X,
+            travelled: 0,
+            path_len: 3,
+        };
+        wall.step(|pos| pos != IVec2::new(1, 0));
+        assert_eq!(wall.pos, IVec2::new(1, 0));
+
+        // a snake that moved its head onto the wall
+        let body = cells(&[(1, 0), (1, 1), (1, 2)]);
+        assert!(wall_hits_snake(wall.pos, wall.pos, &body));
+    }
+
+    #[test]
+    fn head_swapping_places_with_a_wall_is_a_hit() {
+        let body = cells(&[(2, 0), (3, 0), (4, 0)]);
+        assert!(wall_hits_snake(IVec2::new(2, 0), IVec2::new(3, 0), &body));
+        assert!(!wall_hits_snake(IVec2::new(2, 1), IVec2::new(1, 1), &body));
+    }
+}